@@ -0,0 +1,51 @@
+use std::sync::{Condvar, Mutex};
+
+/// Limits the total number of bytes held by multiple threads at once.
+#[derive(Debug)]
+pub struct ByteBudget {
+    capacity: u64,
+    available: Mutex<u64>,
+    freed: Condvar,
+}
+
+/// Returns its bytes to the budget when dropped.
+pub struct BudgetGuard<'a> {
+    budget: &'a ByteBudget,
+    bytes: u64,
+}
+
+impl ByteBudget {
+    pub fn new(capacity: u64) -> Self {
+        Self {
+            capacity,
+            available: Mutex::new(capacity),
+            freed: Condvar::new(),
+        }
+    }
+
+    /// Blocks until `bytes` are available and takes them from the budget.
+    ///
+    /// Requests larger than the whole budget wait until
+    /// the budget is completely free and then take all of it.
+    pub fn acquire(&self, bytes: u64) -> BudgetGuard<'_> {
+        let bytes = bytes.min(self.capacity);
+
+        let mut available = self.available.lock().expect("poisoned");
+        while *available < bytes {
+            available = self.freed.wait(available).expect("poisoned");
+        }
+        *available -= bytes;
+
+        BudgetGuard {
+            budget: self,
+            bytes,
+        }
+    }
+}
+
+impl Drop for BudgetGuard<'_> {
+    fn drop(&mut self) {
+        *self.budget.available.lock().expect("poisoned") += self.bytes;
+        self.budget.freed.notify_all();
+    }
+}
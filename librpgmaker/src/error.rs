@@ -1,12 +1,19 @@
 use std::{
     fmt::Display,
+    io,
     num::ParseIntError,
     path::{PathBuf, StripPrefixError},
 };
 
+use crate::rpg_file::RpgFileType;
+
 /// Represents an Error from the library.
 #[derive(Debug)]
 pub enum Error {
+    /// The given game directory does not exist
+    /// or is not a directory.
+    GameDirNotFound(PathBuf),
+
     /// The System.json file was not found.
     /// This probably means that the given
     /// directory is not a valid RpgMaker game.
@@ -19,6 +26,12 @@ pub enum Error {
     /// See the included error for more details.
     SystemJsonInvalidJson(serde_json::Error),
 
+    /// One of the game's data files (eg. Map001.json) was not valid JSON.
+    DataFileInvalidJson {
+        path: PathBuf,
+        error: serde_json::Error,
+    },
+
     /// The System.json file dod not contain
     /// the included key.
     SystemJsonKeyNotFound { key: String },
@@ -26,6 +39,11 @@ pub enum Error {
     /// The included key was not in the expected format.
     SystemJsonInvalidKey { key: String },
 
+    /// The System.json file was changed by someone else
+    /// after it was read.
+    /// See `RpgGame::set_force_system_json_write()`.
+    SystemJsonModifiedExternally(PathBuf),
+
     /// Stripping a path prefix failed, see error for
     /// more details
     StrixPrefixFailed(StripPrefixError),
@@ -33,19 +51,195 @@ pub enum Error {
     /// Failed to parse a key from System.json
     KeyParseError(ParseIntError),
 
+    /// The key is neither a hex string with an even number of digits nor valid base64.
+    InvalidKey(String),
+
+    /// The key is empty.
+    EmptyKey,
+
+    /// The key does not decrypt the included file.
+    KeyMismatch(PathBuf),
+
+    /// Multiple files would be written to the same output path.
+    OutputPathCollision(Vec<(PathBuf, PathBuf)>),
+
     /// The given output dir already exists.
     OutputDirExists(PathBuf),
 
     /// The game is not encrypted.
     NotEncrypted,
 
+    /// System.json reports that the game is already decrypted,
+    /// see `RpgGame::set_force()`.
+    AlreadyDecrypted,
+
     /// The file is to short to be decrypted
     FileTooShort(PathBuf),
+
+    /// The file is larger than the limit set with `RpgGame::set_max_file_size()`.
+    FileTooLarge { path: PathBuf, size: u64, max: u64 },
+
+    /// The file does not have a known RpgMaker extension.
+    NotRpgFile(PathBuf),
+
+    /// The file is already encrypted.
+    AlreadyEncrypted(PathBuf),
+
+    /// The file is not encrypted.
+    FileNotEncrypted(PathBuf),
+
+    /// The file's contents don't match its type.
+    UnexpectedContent {
+        expected: RpgFileType,
+        path: PathBuf,
+    },
+
+    /// The game contains multiple System.json files with different keys.
+    AmbiguousSystemJson { candidates: Vec<PathBuf> },
+
+    /// The string could not be parsed as `OutputSettings`.
+    InvalidOutputSettings(String),
+
+    /// The game's `.rrdignore` file could not be read or parsed.
+    /// Only returned with the `ignore` feature.
+    RrdIgnore(String),
+
+    /// A decrypted image could not be decoded or encoded.
+    /// Only returned with the `thumbnails` feature.
+    Image(String),
+}
+
+/// A coarse category of an `Error`, see `Error::kind()`.
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    /// The game directory is missing or not usable.
+    Game,
+
+    /// System.json is missing, invalid or ambiguous.
+    SystemJson,
+
+    /// The encryption key is invalid or wrong.
+    Key,
+
+    /// A single file could not be processed.
+    File,
+
+    /// The output could not be written as requested.
+    Output,
+
+    /// Reading or writing failed.
+    Io,
+
+    /// An invalid value was passed in.
+    Input,
+}
+
+/// A summary of an `Error` that, unlike the error itself, can be cloned.
+/// Created with `ErrorInfo::from(&error)`.
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorInfo {
+    pub kind: ErrorKind,
+    /// The error's `Display` output.
+    pub message: String,
+    /// The file or directory the error is about, if any.
+    pub path: Option<PathBuf>,
+}
+
+impl From<&Error> for ErrorInfo {
+    fn from(error: &Error) -> Self {
+        let path = match error {
+            Error::GameDirNotFound(path)
+            | Error::SystemJsonModifiedExternally(path)
+            | Error::KeyMismatch(path)
+            | Error::OutputDirExists(path)
+            | Error::FileTooShort(path)
+            | Error::FileTooLarge { path, .. }
+            | Error::NotRpgFile(path)
+            | Error::AlreadyEncrypted(path)
+            | Error::FileNotEncrypted(path)
+            | Error::UnexpectedContent { path, .. }
+            | Error::DataFileInvalidJson { path, .. } => Some(path.clone()),
+            _ => None,
+        };
+
+        Self {
+            kind: error.kind(),
+            message: error.to_string(),
+            path,
+        }
+    }
+}
+
+impl From<Error> for ErrorInfo {
+    fn from(error: Error) -> Self {
+        Self::from(&error)
+    }
+}
+
+impl Display for ErrorInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error {
+    /// Returns the category of this error.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::GameDirNotFound(_)
+            | Error::NotEncrypted
+            | Error::AlreadyDecrypted
+            | Error::DataFileInvalidJson { .. } => ErrorKind::Game,
+            Error::RrdIgnore(_) => ErrorKind::Game,
+            Error::Image(_) => ErrorKind::File,
+            Error::SystemJsonNotFound
+            | Error::SystemJsonInvalidJson(_)
+            | Error::SystemJsonKeyNotFound { .. }
+            | Error::SystemJsonInvalidKey { .. }
+            | Error::SystemJsonModifiedExternally(_)
+            | Error::AmbiguousSystemJson { .. } => ErrorKind::SystemJson,
+            Error::KeyParseError(_)
+            | Error::InvalidKey(_)
+            | Error::EmptyKey
+            | Error::KeyMismatch(_) => ErrorKind::Key,
+            Error::FileTooShort(_)
+            | Error::FileTooLarge { .. }
+            | Error::NotRpgFile(_)
+            | Error::AlreadyEncrypted(_)
+            | Error::FileNotEncrypted(_)
+            | Error::UnexpectedContent { .. } => ErrorKind::File,
+            Error::StrixPrefixFailed(_)
+            | Error::OutputPathCollision(_)
+            | Error::OutputDirExists(_) => ErrorKind::Output,
+            Error::IoError(_) => ErrorKind::Io,
+            Error::InvalidOutputSettings(_) => ErrorKind::Input,
+        }
+    }
+
+    /// Checks if the operation may succeed when retried, or if only
+    /// a single file failed and the others can still be processed.
+    ///
+    /// Errors about the game itself, like a missing System.json
+    /// or a wrong key, are not recoverable.
+    pub fn is_recoverable(&self) -> bool {
+        match self {
+            Error::IoError(err) => matches!(
+                err.kind(),
+                io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+            ),
+            _ => self.kind() == ErrorKind::File,
+        }
+    }
 }
 
 impl Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let content = match self {
+            Error::GameDirNotFound(path) => {
+                format!("The directory '{}' does not exist.", path.display())
+            }
             Error::SystemJsonNotFound => {
                 format!("The system.json file was not found. Make sure the directory is correct.")
             }
@@ -53,24 +247,87 @@ impl Display for Error {
             Error::SystemJsonInvalidJson(serde_err) => {
                 format!("Failed parsing JSON in system.json: {}", serde_err)
             }
+            Error::DataFileInvalidJson { path, error } => {
+                format!("Failed parsing JSON in '{}': {}", path.display(), error)
+            }
             Error::SystemJsonKeyNotFound { key } => {
                 format!("The key '{}' was not present in system.json", key)
             }
             Error::SystemJsonInvalidKey { key } => {
                 format!("The key '{}' in system.json was an invalid format", key)
             }
+            Error::SystemJsonModifiedExternally(path) => {
+                format!(
+                    "'{}' was modified by another program, refusing to overwrite it",
+                    path.display()
+                )
+            }
             Error::StrixPrefixFailed(err) => format!("{}", err),
             Error::KeyParseError(err) => format!("{}", err),
+            Error::InvalidKey(key) => format!("The key '{}' is not a valid hex or base64 key", key),
+            Error::EmptyKey => "The key is empty".to_string(),
+            Error::KeyMismatch(path) => {
+                format!("The key does not decrypt '{}'", path.display())
+            }
+            Error::OutputPathCollision(collisions) => {
+                let mut content = "The following files would overwrite each other:".to_string();
+                for (a, b) in collisions {
+                    content.push_str(&format!("\n   {} <-> {}", a.display(), b.display()));
+                }
+                content
+            }
             Error::OutputDirExists(path) => {
                 format!("The output directory '{}' already exists!", path.display())
             }
             Error::NotEncrypted => format!("The game is not encrypted"),
+            Error::AlreadyDecrypted => {
+                "The game reports as already decrypted; nothing to do".to_string()
+            }
             Error::FileTooShort(path) => {
                 format!(
                     "The following file was too short to decrypt:\n   -> {}",
                     path.display()
                 )
             }
+            Error::FileTooLarge { path, size, max } => {
+                format!(
+                    "'{}' is {} bytes large, which exceeds the limit of {} bytes",
+                    path.display(),
+                    size,
+                    max
+                )
+            }
+            Error::NotRpgFile(path) => {
+                format!("'{}' is not an RpgMaker file", path.display())
+            }
+            Error::AlreadyEncrypted(path) => {
+                format!("'{}' is already encrypted", path.display())
+            }
+            Error::FileNotEncrypted(path) => {
+                format!("'{}' is not encrypted", path.display())
+            }
+            Error::UnexpectedContent { expected, path } => {
+                format!(
+                    "'{}' does not look like a file of type {:?}",
+                    path.display(),
+                    expected
+                )
+            }
+            Error::AmbiguousSystemJson { candidates } => {
+                let mut content =
+                    "Found multiple System.json files with different keys, pick one of them:"
+                        .to_string();
+                for path in candidates {
+                    content.push_str(&format!("\n   {}", path.display()));
+                }
+                content
+            }
+            Error::InvalidOutputSettings(s) => format!(
+                "'{}' is not a valid output mode, expected one of: in-place, overwrite, rename, specific:<dir>, flatten:<dir>, flatten-depth:<depth>:<dir>, by-type:<dir>, suffixed:<suffix>",
+                s
+            ),
+            Error::RrdIgnore(err) => format!("Invalid .rrdignore file: {}", err),
+            Error::Image(err) => format!("Failed to process image: {}", err),
         };
 
         write!(f, "{}", content)
@@ -94,3 +351,17 @@ impl From<StripPrefixError> for Error {
         Self::StrixPrefixFailed(value)
     }
 }
+
+#[cfg(feature = "ignore")]
+impl From<ignore::Error> for Error {
+    fn from(value: ignore::Error) -> Self {
+        Self::RrdIgnore(value.to_string())
+    }
+}
+
+#[cfg(feature = "thumbnails")]
+impl From<image::ImageError> for Error {
+    fn from(value: image::ImageError) -> Self {
+        Self::Image(value.to_string())
+    }
+}
@@ -0,0 +1,163 @@
+use std::fmt::Display;
+
+use crate::error::Error;
+
+/// A key used to decrypt and encrypt files.
+///
+/// A `Key` is never empty.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Key(Vec<u8>);
+
+/// The length of the keys RpgMaker generates.
+pub(crate) const DEFAULT_KEY_LEN: usize = 16;
+
+/// How a key is written down in System.json.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum KeyEncoding {
+    /// The default, eg. `0f0f0f0f...`
+    Hex,
+
+    /// Used by some modified encrypters, eg. `Dw8PDw8P...`
+    Base64,
+}
+
+/// Where a game's key came from.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum KeySource {
+    /// The `encryptionKey` field in System.json.
+    SystemJson,
+
+    /// An alternate spelling of the `encryptionKey` field in System.json.
+    AlternateField(String),
+
+    /// The key was passed in by the caller.
+    Override,
+}
+
+impl Display for KeySource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KeySource::SystemJson => write!(f, "System.json"),
+            KeySource::AlternateField(field) => write!(f, "System.json (field '{}')", field),
+            KeySource::Override => write!(f, "provided by the caller"),
+        }
+    }
+}
+
+impl Key {
+    /// Parses a key from a hex string, as stored in System.json.
+    ///
+    /// ## Example
+    /// ```
+    /// use librpgmaker::prelude::*;
+    ///
+    /// let key = Key::from_hex("0f0f").unwrap();
+    ///
+    /// assert_eq!(key.as_bytes(), &[0x0f, 0x0f]);
+    /// ```
+    pub fn from_hex(hex: &str) -> Result<Self, Error> {
+        if hex.len() % 2 == 1 || !hex.is_ascii() {
+            return Err(Error::InvalidKey(hex.to_string()));
+        }
+
+        let bytes = (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Self::from_bytes(bytes)
+    }
+
+    /// Parses a key from a standard base64 string, padding is optional.
+    ///
+    /// ## Example
+    /// ```
+    /// use librpgmaker::prelude::*;
+    ///
+    /// let key = Key::from_base64("Dw8=").unwrap();
+    ///
+    /// assert_eq!(key.as_bytes(), &[0x0f, 0x0f]);
+    /// ```
+    pub fn from_base64(base64: &str) -> Result<Self, Error> {
+        let invalid = || Error::InvalidKey(base64.to_string());
+
+        let digits = base64
+            .trim_end_matches('=')
+            .bytes()
+            .map(|c| match c {
+                b'A'..=b'Z' => Ok(c - b'A'),
+                b'a'..=b'z' => Ok(c - b'a' + 26),
+                b'0'..=b'9' => Ok(c - b'0' + 52),
+                b'+' => Ok(62),
+                b'/' => Ok(63),
+                _ => Err(invalid()),
+            })
+            .collect::<Result<Vec<u8>, _>>()?;
+
+        if digits.len() % 4 == 1 {
+            return Err(invalid());
+        }
+
+        // every digit holds 6 bits, every full 8 bits make a byte
+        let mut bytes = Vec::with_capacity(digits.len() * 3 / 4);
+        let mut buffer = 0u32;
+        let mut bits = 0;
+        for digit in digits {
+            buffer = (buffer << 6) | digit as u32;
+            bits += 6;
+            if bits >= 8 {
+                bits -= 8;
+                bytes.push((buffer >> bits) as u8);
+            }
+        }
+
+        Self::from_bytes(bytes)
+    }
+
+    /// Parses a key as stored in System.json, detecting its encoding.
+    ///
+    /// Hex is preferred. Base64 is used if the string is not valid hex,
+    /// or if it only decodes to a key of the usual length as base64.
+    pub fn parse(key: &str) -> Result<(Self, KeyEncoding), Error> {
+        let hex = Self::from_hex(key);
+        if matches!(&hex, Ok(key) if key.0.len() == DEFAULT_KEY_LEN) {
+            return Ok((hex?, KeyEncoding::Hex));
+        }
+
+        match (hex, Self::from_base64(key)) {
+            (_, Ok(key)) if key.0.len() == DEFAULT_KEY_LEN => Ok((key, KeyEncoding::Base64)),
+            (Ok(key), _) => Ok((key, KeyEncoding::Hex)),
+            (Err(e), _) => Err(e),
+        }
+    }
+
+    /// Creates a key from raw bytes.
+    ///
+    /// Fails with `Error::EmptyKey` if `bytes` is empty.
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self, Error> {
+        if bytes.is_empty() {
+            return Err(Error::EmptyKey);
+        }
+
+        Ok(Self(bytes))
+    }
+
+    #[must_use]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Returns the key as a lowercase hex string.
+    #[must_use]
+    pub fn to_hex(&self) -> String {
+        self.0.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+}
+
+impl TryFrom<Vec<u8>> for Key {
+    type Error = Error;
+
+    fn try_from(bytes: Vec<u8>) -> Result<Self, Self::Error> {
+        Self::from_bytes(bytes)
+    }
+}
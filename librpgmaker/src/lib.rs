@@ -1,39 +1,115 @@
 //! A Library to interact with and decrypt RpgMaker games.
 //! To get started, see the `RpgGame` struct.
 
-use error::Error;
-use rayon::prelude::{ParallelBridge, ParallelIterator};
-use rpg_file::{RpgFile, RpgFileType};
+use budget::ByteBudget;
+use error::{Error, ErrorInfo};
+use key::{Key, KeyEncoding, KeySource, DEFAULT_KEY_LEN};
+use observer::{DecryptObserver, ProgressPrinter, Throughput};
+use rayon::prelude::{IntoParallelIterator, ParallelBridge, ParallelIterator};
+use rpg_file::{
+    decrypted_path_of, RpgEngine, RpgEntry, RpgFile, RpgFileType, ScanIndex, SignatureMatch,
+};
+use rrdignore::RrdIgnore;
 use serde_json::Value;
+use sha2::{Digest, Sha256};
 use std::{
-    fs,
-    num::ParseIntError,
+    collections::{HashMap, HashSet},
+    fs, io,
     path::{Path, PathBuf},
-    sync::{atomic::AtomicI64, Arc},
+    str::FromStr,
+    sync::Mutex,
+    time::{Duration, Instant},
 };
-use system_json::SystemJson;
+use system_json::{SystemJson, UTF8_BOM};
 use walkdir::WalkDir;
 
 const SYS_JSON_PATHS: &[&str] = &["www/data/System.json", "data/System.json"];
 const HAS_ENC_AUIDO_KEY: &str = "hasEncryptedAudio";
 const HAS_ENC_IMG_KEY: &str = "hasEncryptedImages";
 const ENCKEY_KEY: &str = "encryptionKey";
+/// Field names the encryption key may be stored under by default, in order of preference.
+/// Some plugins write the key under a variant spelling of `encryptionKey`,
+/// see `RpgGame::with_key_fields()` for others.
+const ENCKEY_KEYS: &[&str] = &[ENCKEY_KEY, "encryptKey", "encryption_key"];
 
+mod budget;
 pub mod error;
+mod key;
+mod observer;
 pub mod prelude;
 mod rpg_file;
+mod rrdignore;
 mod system_json;
 mod tests;
 
 /// Represents an RpgMaker game.
+///
+/// Two `RpgGame`s are equal if their directories are the same
+/// after resolving symlinks, eg. when one was opened through a symlink.
 #[derive(Debug)]
 pub struct RpgGame {
     path: PathBuf,
-    key: Vec<u8>,
+    /// `path` with symlinks resolved, used for comparing games.
+    canonical_path: PathBuf,
+    key: Key,
     orig_key: String,
+    key_source: KeySource,
+    key_encoding: KeyEncoding,
     system_json: SystemJson,
     verbose: bool,
     num_files: Option<usize>,
+    scan_index: Option<ScanIndex>,
+    no_update_encryption: bool,
+    lowercase_output: bool,
+    #[cfg(feature = "serde")]
+    write_index: bool,
+    only_referenced: bool,
+    clear_readonly: bool,
+    max_in_flight_bytes: Option<u64>,
+    max_file_size: Option<u64>,
+    sniff_extensions: bool,
+    force: bool,
+    force_system_json_write: bool,
+    skip_if_decrypted_exists: bool,
+    staged_output: bool,
+    file_mode: Option<u32>,
+    transform: Option<Transform>,
+    signature_match: SignatureMatch,
+    hard_link_unchanged: bool,
+    ignore: RrdIgnore,
+}
+
+impl PartialEq for RpgGame {
+    fn eq(&self, other: &Self) -> bool {
+        self.canonical_path == other.canonical_path
+    }
+}
+
+impl Eq for RpgGame {}
+
+impl std::hash::Hash for RpgGame {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.canonical_path.hash(state);
+    }
+}
+
+/// Resolves symlinks in `path`, falling back to `path` as is
+/// if it doesn't exist (eg. for `RpgGame::from_parts()`).
+fn canonical_path(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// A function applied to the decrypted data before writing it,
+/// see `RpgGame::set_transform()`.
+pub type TransformFn = dyn Fn(&RpgFileType, Vec<u8>) -> Result<Vec<u8>, Error> + Send + Sync;
+
+/// Wraps the `TransformFn` so `RpgGame` can still derive `Debug`.
+struct Transform(Box<TransformFn>);
+
+impl std::fmt::Debug for Transform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Transform")
+    }
 }
 
 /// Configures how to process and store the decrypted files.
@@ -54,6 +130,76 @@ pub enum OutputSettings {
 
     /// Same as output but flattens the dir structure
     Flatten { dir: PathBuf },
+
+    /// Same as output, but only keeps the first `keep_depth` directories
+    /// and flattens the rest of the path like `Flatten`
+    /// (eg. www/img/pictures/x.rpgmvp -> <dir>/www/img/pictures_x.png for a depth of 2).
+    FlattenDepth { dir: PathBuf, keep_depth: usize },
+
+    /// Leaves the game untouched, sorting the files into `<dir>/img`, `<dir>/audio`
+    /// and `<dir>/video` by type and keeping only their file names.
+    /// Files with the same name get a number appended (eg. actor1_1.png).
+    ByType { dir: PathBuf },
+
+    /// Decrypts the game's files next to the encrypted files, inserting the suffix
+    /// before the extension (eg. actor1.rpgmvp -> actor1<suffix>.png).
+    /// Never deletes the encrypted files or overwrites existing ones.
+    InPlaceSuffixed { suffix: String },
+
+    /// Writes the decrypted files next to the encrypted ones and removes each
+    /// encrypted file once its decrypted counterpart is in place
+    /// (eg. actor1.png_ -> actor1.png).
+    InPlaceRename,
+}
+
+impl FromStr for OutputSettings {
+    type Err = Error;
+
+    /// Parses the output settings from a single string.
+    ///
+    /// ## Example
+    /// ```
+    /// use std::path::PathBuf;
+    /// use librpgmaker::prelude::*;
+    ///
+    /// let output: OutputSettings = "flatten:out".parse().unwrap();
+    ///
+    /// assert_eq!(output, OutputSettings::Flatten { dir: PathBuf::from("out") });
+    /// ```
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (mode, arg) = match s.split_once(':') {
+            Some((mode, arg)) => (mode, Some(arg)),
+            None => (s, None),
+        };
+
+        let output = match (mode, arg) {
+            ("in-place", None) => OutputSettings::NextTo,
+            ("overwrite", None) => OutputSettings::Replace,
+            ("rename", None) => OutputSettings::InPlaceRename,
+            ("specific", Some(dir)) if !dir.is_empty() => {
+                OutputSettings::Output { dir: dir.into() }
+            }
+            ("flatten", Some(dir)) if !dir.is_empty() => {
+                OutputSettings::Flatten { dir: dir.into() }
+            }
+            ("flatten-depth", Some(arg)) => match arg.split_once(':') {
+                Some((depth, dir)) if !dir.is_empty() => OutputSettings::FlattenDepth {
+                    dir: dir.into(),
+                    keep_depth: depth
+                        .parse()
+                        .map_err(|_| Error::InvalidOutputSettings(s.to_string()))?,
+                },
+                _ => return Err(Error::InvalidOutputSettings(s.to_string())),
+            },
+            ("by-type", Some(dir)) if !dir.is_empty() => OutputSettings::ByType { dir: dir.into() },
+            ("suffixed", Some(suffix)) if !suffix.is_empty() => OutputSettings::InPlaceSuffixed {
+                suffix: suffix.to_string(),
+            },
+            _ => return Err(Error::InvalidOutputSettings(s.to_string())),
+        };
+
+        Ok(output)
+    }
 }
 
 /// Represents the games encryption key as a raw string
@@ -65,10 +211,100 @@ pub struct RpgKey<'a> {
     pub bytes: &'a [u8],
 }
 
+/// A snapshot of the information about a game, see `RpgGame::summary()`.
+///
+/// Can be serialized with serde by enabling the `serde` feature.
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameSummary {
+    /// The `gameTitle` from System.json.
+    pub title: Option<String>,
+    /// See `RpgGame::version()`.
+    pub version: Option<String>,
+    pub engine: RpgEngine,
+    pub has_encrypted_images: bool,
+    pub has_encrypted_audio: bool,
+    pub key_hex: String,
+    pub system_json_path: PathBuf,
+}
+
+/// Summarizes the outcome of a bulk operation on a game.
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[derive(Debug, Clone, Default)]
+pub struct DecryptReport {
+    /// Number of files that were decrypted.
+    pub decrypted: usize,
+
+    /// Number of files that were copied without changes.
+    pub copied: usize,
+
+    /// Number of files that were left alone, eg. because their
+    /// decrypted version exists or they can't be processed without the key.
+    pub skipped: usize,
+
+    /// Number of decryptable files that were skipped because
+    /// they matched the game's `.rrdignore` file.
+    pub ignored: usize,
+
+    /// Errors for the files that could not be processed.
+    pub failed: Vec<ErrorInfo>,
+
+    /// Number of decrypted bytes written.
+    pub bytes: u64,
+
+    /// Time spent processing the files, not including the setup before the first file.
+    pub duration: Duration,
+
+    /// `bytes` divided by `duration`, 0 if nothing was processed.
+    pub bytes_per_second: f64,
+}
+
+impl DecryptReport {
+    fn set_throughput(&mut self, bytes: u64, duration: Duration) {
+        self.bytes = bytes;
+        self.duration = duration;
+        self.bytes_per_second = match duration.as_secs_f64() {
+            secs if secs > 0.0 => bytes as f64 / secs,
+            _ => 0.0,
+        };
+    }
+}
+
 impl RpgGame {
+    /// Opens the game in the given directory by reading its System.json.
+    ///
+    /// Same as `new(path, false)`.
+    ///
+    /// ## Errors
+    ///
+    /// - `Error::GameDirNotFound` if `path` is not a directory
+    /// - `Error::SystemJsonNotFound` if the directory contains no System.json,
+    ///   which means it's probably not an RpgMaker game
+    /// - `Error::AmbiguousSystemJson` if both `www/data` and `data` contain
+    ///   a System.json and their keys differ
+    /// - `Error::SystemJsonInvalidJson` if System.json could not be parsed
+    /// - `Error::SystemJsonInvalidKey` if the encryption key or flags have an unexpected format
+    /// - `Error::KeyParseError` if the encryption key is not valid hex
+    /// - `Error::EmptyKey` if the encryption key is an empty string
+    /// - `Error::NotEncrypted` if System.json contains no encryption key
+    /// - `Error::RrdIgnore` if the game's `.rrdignore` file is invalid
+    ///   (only with the `ignore` feature)
+    ///
+    /// ## Example
+    /// ```
+    /// use librpgmaker::prelude::*;
+    ///
+    /// let game = RpgGame::open("path/to/game");
+    /// ```
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::new(path, false)
+    }
+
     /// Attempt to create a new `RpgGame` from a given path.
     /// setting `verbose` to true will print decryption progress to stdout
     ///
+    /// See `open()` for the errors this can return.
+    ///
     /// ## Example
     /// ```
     /// use librpgmaker::prelude::*;
@@ -76,19 +312,152 @@ impl RpgGame {
     /// let game = RpgGame::new("path/to/game", false);
     /// ```
     pub fn new<P: AsRef<Path>>(path: P, verbose: bool) -> Result<Self, Error> {
-        let system_json = Self::get_system_json(path.as_ref())?;
-        let (key, orig_key) = Self::try_get_key(&system_json.data)?;
+        Self::with_key_fields(path, ENCKEY_KEYS, verbose)
+    }
+
+    /// Same as `new()`, but looks for the key in System.json under the given
+    /// field names, in order, instead of `encryptionKey`, `encryptKey` and `encryption_key`.
+    ///
+    /// The field the key was found under is returned by `key_source()`.
+    ///
+    /// ## Example
+    /// ```
+    /// use librpgmaker::prelude::*;
+    ///
+    /// let game = RpgGame::with_key_fields("path/to/game", &["encryptionKey", "pluginKey"], false);
+    /// ```
+    pub fn with_key_fields<P: AsRef<Path>>(
+        path: P,
+        key_fields: &[&str],
+        verbose: bool,
+    ) -> Result<Self, Error> {
+        if !path.as_ref().is_dir() {
+            return Err(Error::GameDirNotFound(path.as_ref().to_path_buf()));
+        }
+
+        let system_json = Self::get_system_json(path.as_ref(), key_fields)?;
+        Self::with_loaded_system_json(path, system_json, key_fields, verbose)
+    }
+
+    /// Same as `new()`, but reads the System.json at `system_json_path`
+    /// instead of looking for it in the game directory.
+    ///
+    /// Use this to pick one when `new()` fails with `Error::AmbiguousSystemJson`.
+    pub fn with_system_json<P: AsRef<Path>, Q: AsRef<Path>>(
+        path: P,
+        system_json_path: Q,
+        verbose: bool,
+    ) -> Result<Self, Error> {
+        if !path.as_ref().is_dir() {
+            return Err(Error::GameDirNotFound(path.as_ref().to_path_buf()));
+        }
+
+        let system_json_path = system_json_path.as_ref();
+        let system_json = SystemJson::from_path(system_json_path.to_path_buf())?;
+        Self::with_loaded_system_json(path, system_json, ENCKEY_KEYS, verbose)
+    }
+
+    fn with_loaded_system_json<P: AsRef<Path>>(
+        path: P,
+        system_json: SystemJson,
+        key_fields: &[&str],
+        verbose: bool,
+    ) -> Result<Self, Error> {
+        let (key, orig_key, key_source, key_encoding) =
+            Self::try_get_key(&system_json.data, key_fields)?;
+        let ignore = RrdIgnore::load(path.as_ref())?;
 
         Ok(Self {
             num_files: None,
+            scan_index: None,
+            no_update_encryption: false,
+            lowercase_output: false,
+            #[cfg(feature = "serde")]
+            write_index: false,
+            only_referenced: false,
+            clear_readonly: false,
+            max_in_flight_bytes: None,
+            max_file_size: None,
+            sniff_extensions: false,
+            force: false,
+            force_system_json_write: false,
+            skip_if_decrypted_exists: false,
+            staged_output: false,
+            file_mode: None,
+            transform: None,
+            signature_match: SignatureMatch::default(),
+            hard_link_unchanged: false,
+            ignore,
             verbose,
             key,
             orig_key,
+            key_source,
+            key_encoding,
             system_json,
             path: path.as_ref().to_path_buf(),
+            canonical_path: canonical_path(path.as_ref()),
         })
     }
 
+    /// Create a new `RpgGame` from an already loaded `SystemJson` and key
+    /// instead of reading them from the game directory.
+    ///
+    /// `path` is not checked and only used when walking the game's files,
+    /// its `.rrdignore` file is not read.
+    ///
+    /// ## Example
+    /// ```
+    /// use librpgmaker::prelude::*;
+    ///
+    /// let system_json = SystemJson::from_reader(
+    ///     r#"{"hasEncryptedImages":true}"#.as_bytes(),
+    ///     "data/System.json".into(),
+    /// )
+    /// .unwrap();
+    ///
+    /// let key = Key::from_bytes(vec![0x0f; 16]).unwrap();
+    /// let game = RpgGame::from_parts("path/to/game", system_json, key, false);
+    /// ```
+    pub fn from_parts<P: AsRef<Path>>(
+        path: P,
+        system_json: SystemJson,
+        key: Key,
+        verbose: bool,
+    ) -> Self {
+        let orig_key = key.to_hex();
+
+        Self {
+            num_files: None,
+            scan_index: None,
+            no_update_encryption: false,
+            lowercase_output: false,
+            #[cfg(feature = "serde")]
+            write_index: false,
+            only_referenced: false,
+            clear_readonly: false,
+            max_in_flight_bytes: None,
+            max_file_size: None,
+            sniff_extensions: false,
+            force: false,
+            force_system_json_write: false,
+            skip_if_decrypted_exists: false,
+            staged_output: false,
+            file_mode: None,
+            transform: None,
+            signature_match: SignatureMatch::default(),
+            hard_link_unchanged: false,
+            ignore: RrdIgnore::default(),
+            verbose,
+            key,
+            orig_key,
+            key_source: KeySource::Override,
+            key_encoding: KeyEncoding::Hex,
+            system_json,
+            path: path.as_ref().to_path_buf(),
+            canonical_path: canonical_path(path.as_ref()),
+        }
+    }
+
     /// Scans files in the game directory and returns a list of all files that can decrypted.
     ///
     /// This does not read the file contents, only filename.
@@ -102,6 +471,7 @@ impl RpgGame {
                 Ok(v) => Some(v),
                 Err(_) => None,
             })
+            .filter(|entry| !self.ignore.is_ignored(entry.path()))
             .filter_map(|entry| RpgFileType::scan(entry.path()))
             .collect();
 
@@ -109,195 +479,1492 @@ impl RpgGame {
         Ok(files)
     }
 
-    /// Decrypt all files in the game directory.
+    /// Computes where `decrypt_all()` would write each file for `output`, without
+    /// decrypting or writing anything. Returns `(source, destination)` pairs sorted by source.
     ///
-    /// Returns the number of files decrypted or an error.
+    /// `set_lowercase_output()` is taken into account, `set_sniff_extensions()` is not,
+    /// as it needs the decrypted data. For `ByType` and `InPlaceSuffixed`, colliding names
+    /// are numbered like `decrypt_all()` does, though which file gets the number can differ.
+    /// For the other modes, fails with `Error::OutputPathCollision` if files would
+    /// overwrite each other (eg. `Flatten` with equally named files).
+    pub fn plan(&self, output: &OutputSettings) -> Result<Vec<(PathBuf, PathBuf)>, Error> {
+        let numbered = matches!(
+            output,
+            OutputSettings::ByType { .. } | OutputSettings::InPlaceSuffixed { .. }
+        );
+
+        let mut planned: HashMap<PathBuf, PathBuf> = HashMap::new();
+        let mut collisions = vec![];
+        let mut plan = vec![];
+
+        let in_output_dir = in_dir(output_dir(output));
+        for entry in self.walk()? {
+            if in_output_dir(&entry.path) {
+                continue;
+            }
+
+            let mut new_path = entry.path.with_extension(entry.file_type.to_extension());
+            if self.lowercase_output {
+                new_path = lowercase_file_name(&new_path);
+            }
+
+            let mut dest = output_path(output, &new_path, &entry.file_type, &self.path)?;
+            if numbered {
+                let taken = |path: &Path| planned.contains_key(path) || path.exists();
+                let mut n = 1;
+                let orig_dest = dest.clone();
+                while taken(&dest) {
+                    dest = numbered_path(&orig_dest, n);
+                    n += 1;
+                }
+            }
+
+            if let Some(existing) = planned.insert(dest.clone(), entry.path.clone()) {
+                collisions.push((existing, entry.path.clone()));
+            }
+            plan.push((entry.path, dest));
+        }
+
+        if collisions.is_empty() {
+            Ok(plan)
+        } else {
+            Err(Error::OutputPathCollision(collisions))
+        }
+    }
+
+    /// Lists all decryptable files in the game directory
+    /// along with their size and modification time, sorted by path.
     ///
-    /// When `verbose` is true, the decryption progress will be
-    /// printed to stdout. The total number of files will only
-    /// be displayed if `scan_files()` was run beforehand.
-    pub fn decrypt_all(
-        &mut self,
-        output: &OutputSettings,
-    ) -> Result<Vec<Result<(), Error>>, Error> {
-        let files = WalkDir::new(&self.path)
+    /// Like `scan_files()`, this does not read the file contents.
+    pub fn walk(&self) -> Result<Vec<RpgEntry>, Error> {
+        WalkDir::new(&self.path)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|entry| !self.ignore.is_ignored(entry.path()))
+            .filter_map(|entry| {
+                let file_type = RpgFileType::scan(entry.path())?;
+                Some((entry, file_type))
+            })
+            .map(|(entry, file_type)| {
+                let metadata = entry.metadata().map_err(io::Error::from)?;
+                rpg_entry(entry.into_path(), file_type, metadata)
+            })
+            .collect()
+    }
+
+    /// Same as `walk()`, but reads the metadata of the files on multiple threads,
+    /// which is faster for games with many files.
+    ///
+    /// The result is sorted by path, just like the one of `walk()`.
+    pub fn walk_parallel(&self) -> Result<Vec<RpgEntry>, Error> {
+        let files: Vec<_> = WalkDir::new(&self.path)
             .into_iter()
             .filter_map(Result::ok)
-            .filter_map(|entry| RpgFile::from_path(entry.path()));
+            .filter(|entry| !self.ignore.is_ignored(entry.path()))
+            .filter_map(|entry| {
+                let file_type = RpgFileType::scan(entry.path())?;
+                Some((entry.into_path(), file_type))
+            })
+            .collect();
+
+        let mut entries = files
+            .into_par_iter()
+            .map(|(path, file_type)| {
+                let metadata = fs::symlink_metadata(&path)?;
+                rpg_entry(path, file_type, metadata)
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
 
-        let num_decrypted = Arc::new(AtomicI64::new(0));
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(entries)
+    }
 
-        let results = files
+    /// Lazily reads all decryptable files for which `predicate` returns true.
+    ///
+    /// The predicate is called with the path of each file before it is read,
+    /// so skipped files are never loaded into memory.
+    pub fn files_filtered<'a>(
+        &'a self,
+        predicate: impl Fn(&Path) -> bool + 'a,
+    ) -> impl Iterator<Item = Result<RpgFile, Error>> + 'a {
+        WalkDir::new(&self.path)
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|entry| RpgFileType::scan(entry.path()).is_some())
+            .filter(|entry| !self.ignore.is_ignored(entry.path()))
+            .filter(move |entry| predicate(entry.path()))
+            .map(|entry| RpgFile::try_from(entry.path()))
+    }
+
+    /// Same as `files_filtered(|_| true)`, but reads the files on multiple threads.
+    ///
+    /// Errors are returned per file, so the results can be collected
+    /// into a `Result<Vec<_>, _>` to stop at the first one.
+    pub fn encrypted_files_par(&self) -> impl ParallelIterator<Item = Result<RpgFile, Error>> + '_ {
+        WalkDir::new(&self.path)
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|entry| RpgFileType::scan(entry.path()).is_some())
+            .filter(|entry| !self.ignore.is_ignored(entry.path()))
             .par_bridge()
-            .map(|mut file| -> Result<(), Error> {
-                use std::sync::atomic::Ordering as Ord;
+            .map(|entry| RpgFile::try_from(entry.path()))
+    }
 
+    /// Lazily decrypts every image of the game and scales it down to fit into
+    /// `max_dim` x `max_dim` pixels, returning the original path and the thumbnail as PNG.
+    ///
+    /// Images that are already smaller are only re-encoded, audio and video files are skipped.
+    #[cfg(feature = "thumbnails")]
+    pub fn thumbnails(
+        &self,
+        max_dim: u32,
+    ) -> impl Iterator<Item = Result<(PathBuf, Vec<u8>), Error>> + '_ {
+        self.files_filtered(|path| RpgFileType::scan(path).is_some_and(|t| t.is_image()))
+            .map(move |file| {
+                let mut file = file?;
                 file.decrypt(&self.key)?;
-                let new_path = create_path_from_output(output, &file, &self.path)?;
 
-                num_decrypted.fetch_add(1, Ord::SeqCst);
-                print_progress(
-                    self.num_files,
-                    num_decrypted.load(Ord::SeqCst) as u64,
-                    self.verbose,
-                    &file,
-                    &new_path,
-                );
+                let mut png = vec![];
+                image::load_from_memory(&file.data)?
+                    .thumbnail(max_dim, max_dim)
+                    .write_to(&mut io::Cursor::new(&mut png), image::ImageFormat::Png)?;
 
-                fs::write(&new_path, file.data)?;
-
-                Ok(())
+                Ok((file.orig_path, png))
             })
-            .collect::<Vec<_>>();
+    }
 
-        // in case the files were decrypted in place, we need to update system.json
-        if output == &OutputSettings::Replace {
-            self.system_json.encrypted = false;
+    /// Reads the encrypted file at `rel_path`, relative to the game directory.
+    ///
+    /// Fails with `Error::NotRpgFile` if the extension is not an encrypted one
+    /// and with `Error::FileNotEncrypted` if the data is not encrypted.
+    pub fn open_encrypted<P: AsRef<Path>>(&self, rel_path: P) -> Result<RpgFile, Error> {
+        let file = RpgFile::try_from(self.path.join(rel_path))?;
+        if !file.has_rpg_header_with(self.signature_match) {
+            return Err(Error::FileNotEncrypted(file.orig_path));
         }
-        self.system_json.write()?;
 
-        Ok(results)
+        Ok(file)
     }
 
-    /// Returns the game's decryption key
-    #[must_use]
-    pub fn get_key(&self) -> RpgKey {
-        RpgKey {
-            string: &self.orig_key,
-            bytes: &self.key,
+    /// Reads the decrypted file at `rel_path`, relative to the game directory.
+    ///
+    /// Fails with `Error::NotRpgFile` if the extension is not a decrypted one
+    /// and with `Error::AlreadyEncrypted` if the data is encrypted.
+    /// The `new_path` of the returned file is the path it was read from.
+    pub fn open_decrypted<P: AsRef<Path>>(&self, rel_path: P) -> Result<RpgFile, Error> {
+        let path = self.path.join(rel_path);
+        let file_type =
+            RpgFileType::scan_decrypted(&path).ok_or_else(|| Error::NotRpgFile(path.clone()))?;
+
+        let file = RpgFile {
+            data: fs::read(&path)?,
+            file_type,
+            new_path: path.clone(),
+            orig_path: path,
+        };
+        if file.has_rpg_header_with(self.signature_match) {
+            return Err(Error::AlreadyEncrypted(file.orig_path));
         }
-    }
 
-    /// Indicates if the game reports to be decrypted or not.
-    #[inline]
-    #[must_use]
-    pub fn is_encrypted(&self) -> bool {
-        self.system_json.encrypted
+        Ok(file)
     }
 
-    fn try_get_key(system_json: &Value) -> Result<(Vec<u8>, String), Error> {
-        fn decode_hex(s: &str) -> Result<Vec<u8>, ParseIntError> {
-            (0..s.len())
-                .step_by(2)
-                .map(|i| u8::from_str_radix(&s[i..i + 2], 16))
-                .collect()
+    /// Walks the game directory once and caches the result, see `walk()`.
+    ///
+    /// Later calls return the cached index without touching the disk
+    /// until `rescan()` is called.
+    pub fn scan_index(&mut self) -> Result<&ScanIndex, Error> {
+        if self.scan_index.is_none() {
+            self.rescan()?;
         }
 
-        match system_json.get(ENCKEY_KEY) {
-            Some(key) => match key.as_str() {
-                Some(key) => Ok((decode_hex(key)?, key.to_owned())),
-                None => Err(Error::SystemJsonInvalidKey {
-                    key: key.to_string(),
-                }),
-            },
-            None => Err(Error::NotEncrypted),
-        }
+        Ok(self.scan_index.as_ref().expect("index was just scanned"))
     }
 
-    fn get_system_json(path: &Path) -> Result<SystemJson, Error> {
-        let system_paths: Vec<PathBuf> = SYS_JSON_PATHS
-            .iter()
-            .map(|x| path.join(PathBuf::from(x)))
-            .filter(|path| path.exists())
+    /// Discards the cached index and walks the game directory again.
+    pub fn rescan(&mut self) -> Result<&ScanIndex, Error> {
+        let entries = self.walk()?;
+        self.num_files = Some(entries.len());
+
+        Ok(self.scan_index.insert(ScanIndex { entries }))
+    }
+
+    /// Computes a SHA-256 fingerprint over the relative paths and
+    /// contents of all encrypted files in the game.
+    ///
+    /// Files are processed in sorted path order, so two copies
+    /// of the same game always produce the same fingerprint.
+    pub fn assets_fingerprint(&self) -> Result<[u8; 32], Error> {
+        let mut paths: Vec<PathBuf> = WalkDir::new(&self.path)
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|entry| RpgFileType::scan(entry.path()).is_some())
+            .map(|entry| entry.into_path())
             .collect();
+        paths.sort();
 
-        let Some(system_path) = system_paths.get(0) else {
-            return Err(Error::SystemJsonNotFound);
-        };
+        let mut hasher = Sha256::new();
+        for path in paths {
+            let data = fs::read(&path)?;
+            let rel_path = path.strip_prefix(&self.path)?.to_string_lossy();
 
-        let system = fs::read_to_string(system_path)?;
-        match serde_json::from_str::<Value>(&system) {
-            Ok(v) => Ok(SystemJson {
-                encrypted: check_encrypted(&v)?,
-                data: v,
-                path: system_path.clone(),
-            }),
-            Err(e) => Err(Error::SystemJsonInvalidJson(e)),
+            // length prefixes keep the boundaries between path and data unambiguous
+            hasher.update((rel_path.len() as u64).to_le_bytes());
+            hasher.update(rel_path.as_bytes());
+            hasher.update((data.len() as u64).to_le_bytes());
+            hasher.update(&data);
         }
+
+        Ok(hasher.finalize().into())
     }
-}
 
-fn check_encrypted(value: &Value) -> Result<bool, Error> {
-    let get_key = |key: &str| -> Result<bool, Error> {
-        match value.get(key).unwrap_or(&Value::Bool(false)).as_bool() {
-            Some(v) => Ok(v),
-            None => Err(Error::SystemJsonInvalidKey {
-                key: key.to_string(),
-            }),
+    /// Creates a decrypted copy of the game in `dest`, leaving the game untouched.
+    ///
+    /// All decryptable files are written to `dest` decrypted, every other
+    /// file is copied as is. This includes decryptable files matched by
+    /// the game's `.rrdignore` file, which are counted as ignored.
+    /// The copied System.json is marked as not encrypted, so the copy
+    /// is playable on its own.
+    ///
+    /// With `set_hard_link_unchanged(true)`, the files that are not decrypted
+    /// are hard-linked instead of copied.
+    ///
+    /// Fails with `Error::OutputDirExists` if `dest` already exists.
+    pub fn export_decrypted<P: AsRef<Path>>(&self, dest: P) -> Result<DecryptReport, Error> {
+        enum Exported {
+            Decrypted(u64),
+            Copied,
+            Ignored,
         }
-    };
 
-    let audio = get_key(HAS_ENC_AUIDO_KEY)?;
-    let img = get_key(HAS_ENC_IMG_KEY)?;
+        let dest = dest.as_ref();
+        if dest.exists() {
+            return Err(Error::OutputDirExists(dest.to_path_buf()));
+        }
 
-    Ok(audio || img)
-}
+        let start_time = Instant::now();
+        let in_dest = in_dir(Some(dest));
+        let results = WalkDir::new(&self.path)
+            .into_iter()
+            .filter_entry(|entry| !in_dest(entry.path()))
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_type().is_file())
+            .par_bridge()
+            .map(|entry| -> Result<Exported, Error> {
+                let target = dest.join(entry.path().strip_prefix(&self.path)?);
+                create_dir_all(target.parent().expect("No parent"))?;
 
-fn create_path_from_output(
-    output: &OutputSettings,
-    file: &RpgFile,
-    game_path: &Path,
-) -> Result<PathBuf, Error> {
-    let new_path = match output {
-        OutputSettings::NextTo => file.new_path.clone(),
+                // System.json gets rewritten below, so it must never share the source's inode
+                let link = self.hard_link_unchanged && entry.path() != self.system_json.path;
+
+                if RpgFileType::scan(entry.path()).is_none() {
+                    link_or_copy(entry.path(), &target, link)?;
+                    return Ok(Exported::Copied);
+                }
+
+                if self.ignore.is_ignored(entry.path()) {
+                    link_or_copy(entry.path(), &target, link)?;
+                    return Ok(Exported::Ignored);
+                }
+
+                self.check_file_size(
+                    entry.path(),
+                    entry.metadata().map_err(io::Error::from)?.len(),
+                )?;
+                let mut file = RpgFile::try_from(entry.path())?;
+                file.decrypt(&self.key)?;
+                let target = target.with_extension(file.file_type.to_extension());
+                fs::write(&target, &file.data)?;
+                set_file_mode(&target, self.file_mode)?;
+                Ok(Exported::Decrypted(file.data.len() as u64))
+            })
+            .collect::<Vec<_>>();
 
-        OutputSettings::Replace => {
-            fs::remove_file(&file.orig_path)?;
-            file.new_path.clone()
+        let mut report = DecryptReport::default();
+        let mut bytes = 0;
+        for result in results {
+            match result {
+                Ok(Exported::Decrypted(len)) => {
+                    report.decrypted += 1;
+                    bytes += len;
+                }
+                Ok(Exported::Copied) => report.copied += 1,
+                Ok(Exported::Ignored) => report.ignored += 1,
+                Err(e) => report.failed.push(e.into()),
+            }
         }
 
-        OutputSettings::Output { dir } => {
-            let new_path = dir.join(file.new_path.strip_prefix(game_path)?);
-            fs::create_dir_all(new_path.parent().expect("No parent"))?;
-            new_path
+        let system_json_path = dest.join(self.system_json.path.strip_prefix(&self.path)?);
+        let mut system_json = SystemJson::from_path(system_json_path)?;
+        system_json.encrypted = false;
+        system_json.write()?;
+
+        report.set_throughput(bytes, start_time.elapsed());
+        Ok(report)
+    }
+
+    /// Decrypt all files in the game directory.
+    ///
+    /// Returns the number of files decrypted or an error.
+    ///
+    /// When `verbose` is true, the decryption progress will be
+    /// printed to stdout. The total number of files will only
+    /// be displayed if `scan_files()` was run beforehand.
+    ///
+    /// If System.json reports that the game is not encrypted, fails with
+    /// `Error::AlreadyDecrypted` without looking at any file,
+    /// unless `set_force(true)` was called.
+    pub fn decrypt_all(
+        &mut self,
+        output: &OutputSettings,
+    ) -> Result<Vec<Result<(), Error>>, Error> {
+        match self.verbose {
+            true => {
+                let mut printer = ProgressPrinter {
+                    num_files: self.num_files,
+                    num_decrypted: 0,
+                };
+                self.decrypt_all_with(output, &mut printer)
+            }
+            false => self.decrypt_all_with(output, &mut ()),
         }
+    }
 
-        OutputSettings::Flatten { dir } => {
-            fs::create_dir_all(dir)?;
+    /// Same as `decrypt_all()`, but sums up the results in a `DecryptReport`,
+    /// including the number of bytes written and the throughput.
+    ///
+    /// The time is measured from the first file on, so the setup before
+    /// (eg. `set_only_referenced()` reading the game data) is not included.
+    pub fn decrypt_all_report(&mut self, output: &OutputSettings) -> Result<DecryptReport, Error> {
+        let mut printer = ProgressPrinter {
+            num_files: self.num_files,
+            num_decrypted: 0,
+        };
+        let mut nothing = ();
+        let mut throughput = Throughput {
+            inner: match self.verbose {
+                true => &mut printer,
+                false => &mut nothing,
+            },
+            started: None,
+            bytes: 0,
+            skipped: 0,
+        };
 
-            // FIXME: if there are 2 files with a name that is only different due to non urf-8
-            // characters, this will overwrite the file that came first with later ones
-            // because to_string_lossy() discards any non utf-8 chars.
-            //
-            // Neither OsStr or OsString have a replace() method. the bstr crate would help here,
-            // but adding a whole new crate just for this does not seem worth it.
-            let path_str = file
-                .new_path // test_files/game/www/img/test.png
-                .strip_prefix(game_path) // www/img/test.png
-                .expect("no parent")
-                .to_string_lossy()
-                .replace(std::path::MAIN_SEPARATOR, "_"); // www_img_test.png
+        let results = self.decrypt_all_with(output, &mut throughput)?;
 
-            dir.join(PathBuf::from(path_str)) // output_dir/www_img_test.png
+        let mut report = DecryptReport {
+            skipped: throughput.skipped,
+            ..Default::default()
+        };
+        for result in results {
+            match result {
+                Ok(()) => report.decrypted += 1,
+                Err(e) => report.failed.push(e.into()),
+            }
         }
-    };
+        let duration = throughput.started.map(|started| started.elapsed());
+        report.set_throughput(throughput.bytes, duration.unwrap_or_default());
 
-    Ok(new_path.clone())
-}
+        Ok(report)
+    }
 
-fn print_progress(
-    num_files: Option<usize>,
-    num_decrypted: u64,
-    verbose: bool,
-    file: &RpgFile,
-    new_path: &Path,
-) {
-    match (num_files, verbose) {
-        (Some(num_files), true) => {
-            println!(
-                "[{}/{}] {}\n  -> {}",
-                num_decrypted,
-                num_files,
-                file.orig_path.display(),
-                new_path.display()
-            );
+    /// Restores every encrypted image without using the key (see `RpgFile::restore_image_header()`)
+    /// and writes them according to `output`. This is a last resort for games whose key is lost.
+    ///
+    /// Audio and video files can't be restored without the key and are counted as skipped,
+    /// the restored images as decrypted. System.json is left untouched.
+    pub fn restore_all_images(&self, output: &OutputSettings) -> Result<DecryptReport, Error> {
+        enum Restored {
+            Image(u64),
+            Skipped,
         }
-        (None, true) => println!(
-            "[{}] {}\n  -> {}",
-            num_decrypted,
-            file.orig_path.display(),
-            new_path.display()
-        ),
-        _ => {}
+
+        let start_time = Instant::now();
+        let in_output_dir = in_dir(output_dir(output));
+        let results = WalkDir::new(&self.path)
+            .into_iter()
+            .filter_entry(|entry| !in_output_dir(entry.path()))
+            .filter_map(Result::ok)
+            .filter_map(|entry| Some((RpgFileType::scan(entry.path())?, entry)))
+            .filter(|(_, entry)| !self.ignore.is_ignored(entry.path()))
+            .par_bridge()
+            .map(|(file_type, entry)| -> Result<Restored, Error> {
+                if !file_type.is_image() {
+                    return Ok(Restored::Skipped);
+                }
+
+                let size = entry.metadata().map_err(io::Error::from)?.len();
+                self.check_file_size(entry.path(), size)?;
+
+                let mut file = RpgFile::try_from(entry.path())?;
+                file.restore_image_header()?;
+                if self.lowercase_output {
+                    file.new_path = lowercase_file_name(&file.new_path);
+                }
+
+                let new_path = create_path_from_output(output, &file, &self.path)?;
+                match output {
+                    OutputSettings::InPlaceRename => rename_decrypted(&file, &new_path)?,
+                    _ => write_file(&new_path, &file.data, self.clear_readonly)?,
+                }
+                set_file_mode(&new_path, self.file_mode)?;
+
+                Ok(Restored::Image(file.data.len() as u64))
+            })
+            .collect::<Vec<_>>();
+
+        let mut report = DecryptReport::default();
+        let mut bytes = 0;
+        for result in results {
+            match result {
+                Ok(Restored::Image(len)) => {
+                    report.decrypted += 1;
+                    bytes += len;
+                }
+                Ok(Restored::Skipped) => report.skipped += 1,
+                Err(e) => report.failed.push(e.into()),
+            }
+        }
+
+        report.set_throughput(bytes, start_time.elapsed());
+        Ok(report)
+    }
+
+    /// Same as `decrypt_all()`, but reports every file to `observer`
+    /// instead of printing the progress.
+    pub fn decrypt_all_with(
+        &mut self,
+        output: &OutputSettings,
+        observer: &mut dyn DecryptObserver,
+    ) -> Result<Vec<Result<(), Error>>, Error> {
+        let dir = match output {
+            OutputSettings::Output { dir }
+            | OutputSettings::Flatten { dir }
+            | OutputSettings::FlattenDepth { dir, .. }
+            | OutputSettings::ByType { dir }
+                if self.staged_output =>
+            {
+                dir
+            }
+            _ => return self.decrypt_all_to(output, observer),
+        };
+
+        if dir.exists() {
+            return Err(Error::OutputDirExists(dir.clone()));
+        }
+
+        let mut staging_name = std::ffi::OsString::from(".");
+        staging_name.push(dir.file_name().expect("No file name"));
+        staging_name.push(".rrd-staging");
+        let staging_dir = dir.with_file_name(staging_name);
+        if staging_dir.exists() {
+            fs::remove_dir_all(&staging_dir)?;
+        }
+
+        let mut staged_output = output.clone();
+        if let OutputSettings::Output { dir }
+        | OutputSettings::Flatten { dir }
+        | OutputSettings::FlattenDepth { dir, .. }
+        | OutputSettings::ByType { dir } = &mut staged_output
+        {
+            *dir = staging_dir.clone();
+        }
+
+        let results = self.decrypt_all_to(&staged_output, observer);
+        match &results {
+            Ok(results) if results.iter().all(Result::is_ok) && staging_dir.exists() => {
+                fs::rename(&staging_dir, dir)?
+            }
+            _ if staging_dir.exists() => fs::remove_dir_all(&staging_dir)?,
+            _ => {}
+        }
+
+        results
+    }
+
+    fn decrypt_all_to(
+        &mut self,
+        output: &OutputSettings,
+        observer: &mut dyn DecryptObserver,
+    ) -> Result<Vec<Result<(), Error>>, Error> {
+        if !self.system_json.encrypted && !self.force {
+            return Err(Error::AlreadyDecrypted);
+        }
+
+        if self.lowercase_output {
+            self.check_lowercase_collisions()?;
+        }
+
+        let referenced = match self.only_referenced {
+            true => Some(self.referenced_assets()?),
+            false => None,
+        };
+
+        let in_output_dir = in_dir(output_dir(output));
+        let files = WalkDir::new(&self.path)
+            .into_iter()
+            .filter_entry(|entry| !in_output_dir(entry.path()))
+            .filter_map(Result::ok)
+            .filter(|entry| match &referenced {
+                Some(referenced) => referenced.contains(entry.path()),
+                None => true,
+            })
+            .filter(|entry| RpgFileType::scan(entry.path()).is_some())
+            .filter(|entry| !self.ignore.is_ignored(entry.path()));
+
+        let observer = Mutex::new(observer);
+        let budget = self.max_in_flight_bytes.map(ByteBudget::new);
+
+        let results = files
+            .par_bridge()
+            .map(|entry| {
+                observer.lock().unwrap().on_file_start(entry.path());
+
+                let result = self.decrypt_entry(&entry, output, budget.as_ref());
+                match &result {
+                    Ok(Some((orig_path, new_path, _, bytes))) => observer
+                        .lock()
+                        .unwrap()
+                        .on_file_done(orig_path, new_path, *bytes),
+                    Ok(None) => observer.lock().unwrap().on_file_skipped(entry.path()),
+                    Err(e) => observer.lock().unwrap().on_file_error(entry.path(), e),
+                }
+
+                result.transpose().map(|result| {
+                    result
+                        .map(|(orig_path, new_path, file_type, _)| (orig_path, new_path, file_type))
+                })
+            })
+            .flatten()
+            .collect::<Vec<_>>();
+
+        #[cfg(feature = "serde")]
+        if self.write_index {
+            self.write_index(output, &results)?;
+        }
+
+        let results = results
+            .into_iter()
+            .map(|result| result.map(|_| ()))
+            .collect();
+
+        if self.no_update_encryption {
+            return Ok(results);
+        }
+
+        // the workers above only borrow self immutably and have all finished here,
+        // so system.json is written exactly once, from this thread.
+        // in case the files were decrypted in place, we need to update it
+        if matches!(
+            output,
+            OutputSettings::Replace | OutputSettings::InPlaceRename
+        ) {
+            self.system_json.encrypted = false;
+        }
+        self.write_system_json()?;
+
+        Ok(results)
+    }
+
+    /// Decrypts a single file for `decrypt_all_with()`, returning the original path,
+    /// the path it was written to, its type and the number of bytes written.
+    ///
+    /// Returns `None` if the file was skipped because its decrypted version exists.
+    fn decrypt_entry(
+        &self,
+        entry: &walkdir::DirEntry,
+        output: &OutputSettings,
+        budget: Option<&ByteBudget>,
+    ) -> Result<Option<(PathBuf, PathBuf, RpgFileType, u64)>, Error> {
+        let decrypted_exists = |new_path: &Path, file_type: &RpgFileType| -> Result<bool, Error> {
+            let new_path = match self.lowercase_output {
+                true => lowercase_file_name(new_path),
+                false => new_path.to_path_buf(),
+            };
+            Ok(output_path(output, &new_path, file_type, &self.path)?.exists())
+        };
+
+        // without sniffing, the decrypted path is known without reading the file
+        if self.skip_if_decrypted_exists && !self.sniff_extensions {
+            if let (Some(new_path), Some(file_type)) = (
+                decrypted_path_of(entry.path()),
+                RpgFileType::scan(entry.path()),
+            ) {
+                if decrypted_exists(&new_path, &file_type)? {
+                    return Ok(None);
+                }
+            }
+        }
+
+        let size = entry.metadata().map_err(io::Error::from)?.len();
+        self.check_file_size(entry.path(), size)?;
+
+        // held until the file's data is dropped at the end of this function
+        let _guard = budget.map(|budget| budget.acquire(size));
+
+        let mut file = RpgFile::try_from(entry.path())?;
+        file.decrypt(&self.key)?;
+        if let Some(Transform(transform)) = &self.transform {
+            file.data = transform(&file.file_type, std::mem::take(&mut file.data))?;
+        }
+        if self.sniff_extensions {
+            if let Some(ext) = file.sniff_extension() {
+                file.new_path.set_extension(ext);
+            }
+            if self.skip_if_decrypted_exists && decrypted_exists(&file.new_path, &file.file_type)? {
+                return Ok(None);
+            }
+        }
+        if self.lowercase_output {
+            file.new_path = lowercase_file_name(&file.new_path);
+        }
+        let new_path = create_path_from_output(output, &file, &self.path)?;
+
+        match output {
+            OutputSettings::InPlaceRename => rename_decrypted(&file, &new_path)?,
+            _ => write_file(&new_path, &file.data, self.clear_readonly)?,
+        }
+        set_file_mode(&new_path, self.file_mode)?;
+
+        let bytes = file.data.len() as u64;
+        Ok(Some((file.orig_path, new_path, file.file_type, bytes)))
+    }
+
+    /// When set to true, `decrypt_all()` leaves System.json untouched,
+    /// regardless of the `OutputSettings` used.
+    pub fn set_no_update_encryption(&mut self, no_update_encryption: bool) {
+        self.no_update_encryption = no_update_encryption;
+    }
+
+    /// Decrypts a single file, given relative to the game directory,
+    /// and returns its data as `Bytes`.
+    ///
+    /// Nothing is written to disk.
+    #[cfg(feature = "bytes")]
+    pub fn decrypt_to_bytes<P: AsRef<Path>>(&self, rel_path: P) -> Result<bytes::Bytes, Error> {
+        let mut file = RpgFile::try_from(self.path.join(rel_path))?;
+        file.decrypt(&self.key)?;
+        Ok(file.into_bytes())
+    }
+
+    /// When set to true, `decrypt_all()` lowercases the file names of the
+    /// decrypted files. Directory names are left as they are.
+    ///
+    /// This prevents files like `Actor1.png` and `actor1.png` overwriting
+    /// each other on case-insensitive filesystems. If the game contains such
+    /// files, `decrypt_all()` fails with `Error::OutputPathCollision` before
+    /// decrypting anything.
+    pub fn set_lowercase_output(&mut self, lowercase_output: bool) {
+        self.lowercase_output = lowercase_output;
+    }
+
+    /// Returns the paths of all decryptable files that are referenced
+    /// by the game's data files (maps, actors, System.json etc.).
+    ///
+    /// A file counts as referenced if its name without extension appears
+    /// as a string anywhere in one of the json files next to System.json,
+    /// which is how RpgMaker refers to assets. Assets only used by plugins
+    /// are not detected.
+    pub fn referenced_assets(&self) -> Result<HashSet<PathBuf>, Error> {
+        fn collect_strings(value: &Value, strings: &mut HashSet<String>) {
+            match value {
+                Value::String(s) => {
+                    strings.insert(s.clone());
+                }
+                Value::Array(values) => values.iter().for_each(|v| collect_strings(v, strings)),
+                Value::Object(map) => map.values().for_each(|v| collect_strings(v, strings)),
+                _ => {}
+            }
+        }
+
+        let data_dir = self.system_json.path.parent().expect("No parent");
+        let mut strings = HashSet::new();
+
+        for entry in fs::read_dir(data_dir)? {
+            let path = entry?.path();
+            if path.extension() != Some("json".as_ref()) {
+                continue;
+            }
+
+            let data = fs::read(&path)?;
+            let data = data.strip_prefix(UTF8_BOM).unwrap_or(&data);
+            let value = serde_json::from_slice(data)
+                .map_err(|error| Error::DataFileInvalidJson { path, error })?;
+            collect_strings(&value, &mut strings);
+        }
+
+        let referenced = WalkDir::new(&self.path)
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|entry| RpgFileType::scan(entry.path()).is_some())
+            .filter(|entry| {
+                let stem = entry.path().file_stem().unwrap_or_default();
+                strings.contains(stem.to_string_lossy().as_ref())
+            })
+            .map(|entry| entry.into_path())
+            .collect();
+
+        Ok(referenced)
+    }
+
+    /// When set to true, `decrypt_all()` only decrypts the files returned
+    /// by `referenced_assets()`, skipping assets the game never uses.
+    pub fn set_only_referenced(&mut self, only_referenced: bool) {
+        self.only_referenced = only_referenced;
+    }
+
+    /// When set to true, `decrypt_all()` makes read-only files it
+    /// needs to overwrite writable instead of failing.
+    pub fn set_clear_readonly(&mut self, clear_readonly: bool) {
+        self.clear_readonly = clear_readonly;
+    }
+
+    /// Limits the total size of the files `decrypt_all()` holds in memory
+    /// at the same time across all threads.
+    ///
+    /// Files larger than the limit are still decrypted, but only
+    /// while no other file is being processed.
+    pub fn set_max_in_flight_bytes(&mut self, max_in_flight_bytes: Option<u64>) {
+        self.max_in_flight_bytes = max_in_flight_bytes;
+    }
+
+    /// When set to true, `export_decrypted()` hard-links the files it would otherwise
+    /// copy unchanged (everything except System.json), which saves space and time
+    /// for large games. Falls back to copying where linking fails, eg. when `dest`
+    /// is on a different filesystem.
+    ///
+    /// Changes to a linked file in the export also change the original.
+    pub fn set_hard_link_unchanged(&mut self, hard_link_unchanged: bool) {
+        self.hard_link_unchanged = hard_link_unchanged;
+    }
+
+    /// Sets how much of the RpgMaker signature `open_encrypted()` and `open_decrypted()`
+    /// require to consider a file encrypted. Defaults to `SignatureMatch::Prefix`.
+    pub fn set_signature_match(&mut self, signature_match: SignatureMatch) {
+        self.signature_match = signature_match;
+    }
+
+    /// Makes `decrypt_all()` pass the decrypted data of every file through `transform`
+    /// and write its result instead, eg. to optimize images. `None` writes the data as is.
+    ///
+    /// The transform runs in parallel for different files. With `set_sniff_extensions(true)`,
+    /// the extension is picked based on the transformed data.
+    pub fn set_transform(&mut self, transform: Option<Box<TransformFn>>) {
+        self.transform = transform.map(Transform);
+    }
+
+    /// Makes `decrypt_all()` and `export_decrypted()` fail with `Error::FileTooLarge`
+    /// for files larger than `max_file_size` bytes instead of reading them into memory.
+    ///
+    /// The failures end up in the results like any other error for a single file.
+    pub fn set_max_file_size(&mut self, max_file_size: Option<u64>) {
+        self.max_file_size = max_file_size;
+    }
+
+    fn check_file_size(&self, path: &Path, size: u64) -> Result<(), Error> {
+        match self.max_file_size {
+            Some(max) if size > max => Err(Error::FileTooLarge {
+                path: path.to_path_buf(),
+                size,
+                max,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Sets the permissions of every decrypted file written by `decrypt_all()`
+    /// or `export_decrypted()` to `file_mode` (eg. `0o644`), regardless of the umask.
+    ///
+    /// Only has an effect on Unix.
+    pub fn set_file_mode(&mut self, file_mode: Option<u32>) {
+        self.file_mode = file_mode;
+    }
+
+    /// When set to true, `decrypt_all()` skips every file whose decrypted
+    /// version already exists at its output path. Skipped files are reported
+    /// to `DecryptObserver::on_file_skipped()` and left out of the results.
+    ///
+    /// With `set_sniff_extensions(true)`, the files still have to be read
+    /// and decrypted to know their output path.
+    pub fn set_skip_if_decrypted_exists(&mut self, skip_if_decrypted_exists: bool) {
+        self.skip_if_decrypted_exists = skip_if_decrypted_exists;
+    }
+
+    /// When set to true, `decrypt_all()` writes the files for the `Output`, `Flatten`,
+    /// `FlattenDepth` and `ByType` modes into a staging directory next to the output directory,
+    /// which is only renamed to the output directory once every file was
+    /// decrypted. If any file fails, the staging directory is removed and the
+    /// output directory never exists half written.
+    ///
+    /// The output directory must not exist yet, otherwise `decrypt_all()` fails
+    /// with `Error::OutputDirExists`. Observers see the paths in the staging directory.
+    pub fn set_staged_output(&mut self, staged_output: bool) {
+        self.staged_output = staged_output;
+    }
+
+    /// When set to true, `decrypt_all()` decrypts the game's files
+    /// even if System.json reports that the game is not encrypted.
+    pub fn set_force(&mut self, force: bool) {
+        self.force = force;
+    }
+
+    /// When set to true, System.json is written even if it was changed
+    /// by someone else after it was read, instead of failing with
+    /// `Error::SystemJsonModifiedExternally`. The external changes are lost.
+    pub fn set_force_system_json_write(&mut self, force_system_json_write: bool) {
+        self.force_system_json_write = force_system_json_write;
+    }
+
+    /// When set to true, `decrypt_all()` picks the extension of the decrypted
+    /// files based on their contents (see `RpgFile::sniff_extension()`)
+    /// instead of only their type, falling back to the default extension.
+    pub fn set_sniff_extensions(&mut self, sniff_extensions: bool) {
+        self.sniff_extensions = sniff_extensions;
+    }
+
+    /// When set to true, `decrypt_all()` writes an `index.json` file which maps
+    /// the path of every encrypted file to the path of its decrypted version.
+    ///
+    /// Paths are relative to the game directory and the output directory respectively.
+    /// The index is placed in the output directory, or in the game directory if
+    /// the files are decrypted in place.
+    ///
+    /// Only available with the `serde` feature.
+    #[cfg(feature = "serde")]
+    pub fn set_write_index(&mut self, write_index: bool) {
+        self.write_index = write_index;
+    }
+
+    #[cfg(feature = "serde")]
+    fn write_index(
+        &self,
+        output: &OutputSettings,
+        results: &[Result<(PathBuf, PathBuf, RpgFileType), Error>],
+    ) -> Result<(), Error> {
+        let output_dir = output_dir(output).unwrap_or(&self.path);
+
+        let mut index = serde_json::Map::new();
+        for (orig_path, new_path, file_type) in results.iter().filter_map(|r| r.as_ref().ok()) {
+            index.insert(
+                orig_path.strip_prefix(&self.path)?.to_string_lossy().into(),
+                serde_json::json!({
+                    "path": new_path.strip_prefix(output_dir)?.to_string_lossy(),
+                    "type": file_type.name(),
+                }),
+            );
+        }
+
+        create_dir_all(output_dir)?;
+        let index = Value::Object(index).to_string();
+        Ok(fs::write(output_dir.join("index.json"), index)?)
+    }
+
+    fn check_lowercase_collisions(&self) -> Result<(), Error> {
+        let mut seen: HashMap<PathBuf, PathBuf> = HashMap::new();
+        let mut collisions = vec![];
+
+        let files = WalkDir::new(&self.path)
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|entry| !self.ignore.is_ignored(entry.path()))
+            .filter_map(|entry| {
+                let file_type = RpgFileType::scan(entry.path())?;
+                Some(entry.path().with_extension(file_type.to_extension()))
+            });
+
+        for path in files {
+            let lowercase = lowercase_file_name(&path);
+            if let Some(existing) = seen.insert(lowercase, path.clone()) {
+                collisions.push((existing, path));
+            }
+        }
+
+        if collisions.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::OutputPathCollision(collisions))
+        }
+    }
+
+    /// Checks if the game's key actually decrypts its files by decrypting
+    /// the header of the first encrypted image found.
+    ///
+    /// Returns `Error::KeyMismatch` if the decrypted header is not a PNG header.
+    /// If the game contains no encrypted images, the key is assumed to be correct.
+    pub fn validate_key(&self) -> Result<(), Error> {
+        let sample = WalkDir::new(&self.path)
+            .into_iter()
+            .filter_map(Result::ok)
+            .find(|entry| RpgFileType::scan(entry.path()).is_some_and(|t| t.is_image()));
+
+        let Some(sample) = sample else {
+            return Ok(());
+        };
+
+        let file = RpgFile::try_from(sample.path())?;
+        if file.decrypts_with(&self.key) {
+            Ok(())
+        } else {
+            Err(Error::KeyMismatch(file.orig_path))
+        }
+    }
+
+    /// Returns the parsed decryption key, eg. to format it with `Key::to_hex()`.
+    #[inline]
+    #[must_use]
+    pub fn key(&self) -> &Key {
+        &self.key
+    }
+
+    /// Returns the game's decryption key
+    #[must_use]
+    pub fn get_key(&self) -> RpgKey {
+        RpgKey {
+            string: &self.orig_key,
+            bytes: self.key.as_bytes(),
+        }
+    }
+
+    /// Sets both `hasEncryptedAudio` and `hasEncryptedImages` in System.json
+    /// and writes it to disk once.
+    ///
+    /// Fails with `Error::SystemJsonModifiedExternally` if System.json
+    /// was changed by someone else after it was read, unless
+    /// `set_force_system_json_write(true)` was called.
+    pub fn set_encrypted(&mut self, encrypted: bool) -> Result<(), Error> {
+        self.system_json.set_decrypt(encrypted)?;
+        self.write_system_json()
+    }
+
+    fn write_system_json(&mut self) -> Result<(), Error> {
+        match self.force_system_json_write {
+            true => self.system_json.force_write(),
+            false => self.system_json.write(),
+        }
+    }
+
+    /// Returns the game directory this `RpgGame` was opened from.
+    #[inline]
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the path of the System.json the game was loaded from.
+    #[inline]
+    #[must_use]
+    pub fn system_json_path(&self) -> &Path {
+        &self.system_json.path
+    }
+
+    /// Checks if the key has the 16 bytes RpgMaker generates.
+    ///
+    /// Other lengths still work, as the key is repeated over the header,
+    /// but usually mean that the key was not read correctly.
+    #[must_use]
+    pub fn key_looks_valid(&self) -> bool {
+        self.key.as_bytes().len() == DEFAULT_KEY_LEN
+    }
+
+    /// Indicates if the game reports to be decrypted or not.
+    #[inline]
+    #[must_use]
+    pub fn is_encrypted(&self) -> bool {
+        self.system_json.encrypted
+    }
+
+    /// Collects the game's title, engine, encryption flags and key.
+    ///
+    /// The engine is derived from the location of System.json,
+    /// which MV games keep in `www/data` and MZ games in `data`.
+    pub fn summary(&self) -> GameSummary {
+        let flag = |key: &str| {
+            self.system_json
+                .data
+                .get(key)
+                .and_then(flag_as_bool)
+                .unwrap_or(false)
+        };
+
+        let engine = match self.system_json.path.strip_prefix(&self.path) {
+            Ok(path)
+                if path
+                    .components()
+                    .next()
+                    .is_some_and(|dir| dir.as_os_str().eq_ignore_ascii_case("www")) =>
+            {
+                RpgEngine::Mv
+            }
+            _ => RpgEngine::Mz,
+        };
+
+        GameSummary {
+            title: self
+                .system_json
+                .data
+                .get("gameTitle")
+                .and_then(Value::as_str)
+                .map(str::to_string),
+            version: self.version(),
+            engine,
+            has_encrypted_images: flag(HAS_ENC_IMG_KEY),
+            has_encrypted_audio: flag(HAS_ENC_AUIDO_KEY),
+            key_hex: self.key.to_hex(),
+            system_json_path: self.system_json.path.clone(),
+        }
+    }
+
+    /// Returns the game's version: the `versionId` from System.json,
+    /// or else the `version` from the `package.json` in the game directory.
+    pub fn version(&self) -> Option<String> {
+        let as_string = |value: &Value| match value {
+            Value::String(version) => Some(version.clone()),
+            Value::Number(version) => Some(version.to_string()),
+            _ => None,
+        };
+
+        if let Some(version) = self.system_json.data.get("versionId").and_then(as_string) {
+            return Some(version);
+        }
+
+        let package_json = fs::read(self.path.join("package.json")).ok()?;
+        let package_json: Value = serde_json::from_slice(&package_json).ok()?;
+        package_json.get("version").and_then(as_string)
+    }
+
+    /// Returns how the game's key is encoded in System.json.
+    #[inline]
+    #[must_use]
+    pub fn key_encoding(&self) -> KeyEncoding {
+        self.key_encoding
+    }
+
+    /// Returns where the game's key came from.
+    #[inline]
+    #[must_use]
+    pub fn key_source(&self) -> &KeySource {
+        &self.key_source
+    }
+
+    fn try_get_key(
+        system_json: &Value,
+        key_fields: &[&str],
+    ) -> Result<(Key, String, KeySource, KeyEncoding), Error> {
+        let found = key_fields
+            .iter()
+            .find_map(|field| Some((*field, system_json.get(field)?)));
+
+        match found {
+            Some((field, key)) => match key.as_str() {
+                Some(key) => {
+                    let source = match field {
+                        ENCKEY_KEY => KeySource::SystemJson,
+                        field => KeySource::AlternateField(field.to_string()),
+                    };
+                    let (parsed, encoding) = Key::parse(key)?;
+                    Ok((parsed, key.to_owned(), source, encoding))
+                }
+                None => Err(Error::SystemJsonInvalidKey {
+                    key: key.to_string(),
+                }),
+            },
+            None => Err(Error::NotEncrypted),
+        }
+    }
+
+    fn get_system_json(path: &Path, key_fields: &[&str]) -> Result<SystemJson, Error> {
+        let system_paths: Vec<PathBuf> = SYS_JSON_PATHS
+            .iter()
+            .filter_map(|x| find_dirs_case_insensitive(path, Path::new(x)))
+            .filter(|path| path.exists())
+            .collect();
+
+        let Some(system_path) = system_paths.get(0) else {
+            return Err(Error::SystemJsonNotFound);
+        };
+
+        let system_json = SystemJson::from_path(system_path.clone())?;
+
+        // broken re-packs sometimes contain both files with different keys,
+        // picking one of them silently could decrypt everything with the wrong key
+        for other_path in &system_paths[1..] {
+            let other = SystemJson::from_path(other_path.clone())?;
+            let key = |data: &Value| {
+                Self::try_get_key(data, key_fields)
+                    .ok()
+                    .map(|(key, ..)| key)
+            };
+
+            if key(&system_json.data) != key(&other.data) {
+                return Err(Error::AmbiguousSystemJson {
+                    candidates: system_paths,
+                });
+            }
+        }
+
+        Ok(system_json)
+    }
+}
+
+/// Finds all games below `root` (including `root` itself) by looking for directories
+/// that contain a System.json, and opens them with `RpgGame::new()`.
+///
+/// Directories inside a game are not searched, so a game's `www` folder is not
+/// mistaken for another game. Games that fail to open (eg. because they are not
+/// encrypted) are returned as errors, the search continues after them.
+///
+/// ## Example
+/// ```no_run
+/// use librpgmaker::prelude::*;
+///
+/// for game in find_games("path/to/games") {
+///     match game {
+///         Ok(game) => println!("{}", game.path().display()),
+///         Err(e) => eprintln!("{}", e),
+///     }
+/// }
+/// ```
+pub fn find_games<P: AsRef<Path>>(root: P) -> impl Iterator<Item = Result<RpgGame, Error>> {
+    let mut walker = WalkDir::new(root).sort_by_file_name().into_iter();
+
+    std::iter::from_fn(move || loop {
+        let entry = match walker.next()? {
+            Ok(entry) => entry,
+            Err(e) => return Some(Err(io::Error::from(e).into())),
+        };
+        if !entry.file_type().is_dir() {
+            continue;
+        }
+
+        let is_game = SYS_JSON_PATHS.iter().any(|sys_path| {
+            find_dirs_case_insensitive(entry.path(), Path::new(sys_path))
+                .is_some_and(|path| path.is_file())
+        });
+        if is_game {
+            walker.skip_current_dir();
+            return Some(RpgGame::new(entry.path(), false));
+        }
+    })
+}
+
+pub(crate) fn check_encrypted(value: &Value) -> Result<bool, Error> {
+    let get_key = |key: &str| -> Result<bool, Error> {
+        match value.get(key).map_or(Some(false), flag_as_bool) {
+            Some(v) => Ok(v),
+            None => Err(Error::SystemJsonInvalidKey {
+                key: key.to_string(),
+            }),
+        }
+    };
+
+    let audio = get_key(HAS_ENC_AUIDO_KEY)?;
+    let img = get_key(HAS_ENC_IMG_KEY)?;
+
+    Ok(audio || img)
+}
+
+fn rpg_entry(
+    path: PathBuf,
+    file_type: RpgFileType,
+    metadata: fs::Metadata,
+) -> Result<RpgEntry, Error> {
+    Ok(RpgEntry {
+        path,
+        file_type,
+        size: metadata.len(),
+        modified: metadata.modified()?,
+    })
+}
+
+/// Reads an encryption flag. Besides proper bools, hand-edited System.json
+/// files sometimes contain `"true"`/`"false"` strings or `0`/`1`.
+fn flag_as_bool(value: &Value) -> Option<bool> {
+    match value {
+        Value::Bool(v) => Some(*v),
+        Value::String(s) if s.eq_ignore_ascii_case("true") => Some(true),
+        Value::String(s) if s.eq_ignore_ascii_case("false") => Some(false),
+        Value::Number(n) => match n.as_u64() {
+            Some(0) => Some(false),
+            Some(1) => Some(true),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn create_path_from_output(
+    output: &OutputSettings,
+    file: &RpgFile,
+    game_path: &Path,
+) -> Result<PathBuf, Error> {
+    let new_path = output_path(output, &file.new_path, &file.file_type, game_path)?;
+
+    match output {
+        OutputSettings::NextTo | OutputSettings::InPlaceRename => {}
+        OutputSettings::Replace => fs::remove_file(&file.orig_path)?,
+        OutputSettings::Output { .. } | OutputSettings::FlattenDepth { .. } => {
+            create_dir_all(new_path.parent().expect("No parent"))?
+        }
+        OutputSettings::Flatten { dir } => create_dir_all(dir)?,
+        OutputSettings::ByType { .. } => {
+            create_dir_all(new_path.parent().expect("No parent"))?;
+            return Ok(reserve_unique_path(&new_path)?);
+        }
+        OutputSettings::InPlaceSuffixed { .. } => return Ok(reserve_unique_path(&new_path)?),
+    }
+
+    Ok(new_path)
+}
+
+/// Returns the directory `output` writes into, if it doesn't write into the game directory.
+fn output_dir(output: &OutputSettings) -> Option<&Path> {
+    match output {
+        OutputSettings::NextTo
+        | OutputSettings::Replace
+        | OutputSettings::InPlaceSuffixed { .. }
+        | OutputSettings::InPlaceRename => None,
+        OutputSettings::Output { dir }
+        | OutputSettings::Flatten { dir }
+        | OutputSettings::FlattenDepth { dir, .. }
+        | OutputSettings::ByType { dir } => Some(dir),
+    }
+}
+
+/// Returns a check for paths inside `dir`, used to keep the walks over the game
+/// directory out of an output directory nested in it, so written files are not picked up.
+fn in_dir(dir: Option<&Path>) -> impl Fn(&Path) -> bool {
+    let dir = dir.and_then(|dir| std::path::absolute(dir).ok());
+
+    move |path| match &dir {
+        Some(dir) => std::path::absolute(path).is_ok_and(|path| path.starts_with(dir)),
+        None => false,
+    }
+}
+
+/// Returns where a file decrypted to `new_path` is written for `output`,
+/// without touching the disk. `InPlaceSuffixed` and `ByType` paths are not made unique.
+fn output_path(
+    output: &OutputSettings,
+    new_path: &Path,
+    file_type: &RpgFileType,
+    game_path: &Path,
+) -> Result<PathBuf, Error> {
+    let output_path = match output {
+        OutputSettings::NextTo | OutputSettings::Replace | OutputSettings::InPlaceRename => {
+            new_path.to_path_buf()
+        }
+
+        OutputSettings::Output { dir } => dir.join(new_path.strip_prefix(game_path)?),
+
+        OutputSettings::Flatten { dir } => {
+            // FIXME: if there are 2 files with a name that is only different due to non urf-8
+            // characters, this will overwrite the file that came first with later ones
+            // because to_string_lossy() discards any non utf-8 chars.
+            //
+            // Neither OsStr or OsString have a replace() method. the bstr crate would help here,
+            // but adding a whole new crate just for this does not seem worth it.
+            let path_str = flatten_path(
+                new_path // test_files/game/www/img/test.png
+                    .strip_prefix(game_path)?, // www/img/test.png
+            ); // www_img_test.png
+
+            dir.join(PathBuf::from(path_str)) // output_dir/www_img_test.png
+        }
+
+        OutputSettings::FlattenDepth { dir, keep_depth } => {
+            let rel_path = new_path.strip_prefix(game_path)?; // www/img/pictures/test.png
+            let keep_depth = (*keep_depth).min(rel_path.components().count() - 1);
+
+            let kept: PathBuf = rel_path.components().take(keep_depth).collect(); // www/img
+            let flattened: PathBuf = rel_path.components().skip(keep_depth).collect(); // pictures/test.png
+
+            dir.join(kept).join(flatten_path(&flattened)) // output_dir/www/img/pictures_test.png
+        }
+
+        OutputSettings::ByType { dir } => {
+            let type_dir = match file_type {
+                RpgFileType::Image => "img",
+                RpgFileType::Audio => "audio",
+                RpgFileType::Video => "video",
+            };
+            dir.join(type_dir)
+                .join(new_path.file_name().expect("No file name"))
+        }
+
+        OutputSettings::InPlaceSuffixed { suffix } => {
+            let mut file_name = new_path.file_stem().expect("No file name").to_owned();
+            file_name.push(suffix);
+            file_name.push(".");
+            file_name.push(new_path.extension().unwrap_or_default());
+
+            new_path.with_file_name(file_name)
+        }
+    };
+
+    Ok(output_path)
+}
+
+/// Joins the components of `path` with `_`, turning it into a single file name.
+///
+/// Both `/` and `\` are treated as separators on every platform. Underscores
+/// that are already in the path are escaped as `%5F` (and `%` as `%25`),
+/// so every `_` in the result is a separator and the original path can be recovered.
+fn flatten_path(path: &Path) -> String {
+    path.components()
+        .map(|component| {
+            component
+                .as_os_str()
+                .to_string_lossy()
+                .replace('%', "%25")
+                .replace('_', "%5F")
+                .replace('\\', "_")
+        })
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+/// Writes the decrypted data of `file` to a temporary file, moves it to `new_path`
+/// and only then removes the encrypted original. If anything fails before the move,
+/// the original is left untouched.
+fn rename_decrypted(file: &RpgFile, new_path: &Path) -> Result<(), Error> {
+    let mut tmp_name = new_path.file_name().expect("No file name").to_owned();
+    tmp_name.push(".rrd-tmp");
+    let tmp_path = new_path.with_file_name(tmp_name);
+
+    if let Err(e) = fs::write(&tmp_path, &file.data).and_then(|_| fs::rename(&tmp_path, new_path)) {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(e.into());
+    }
+
+    fs::remove_file(&file.orig_path)?;
+    Ok(())
+}
+
+/// Writes `data` to `path`. If the file exists and is read-only,
+/// it is made writable and the write retried when `clear_readonly` is true.
+fn write_file(path: &Path, data: &[u8], clear_readonly: bool) -> Result<(), io::Error> {
+    match fs::write(path, data) {
+        Err(e) if clear_readonly && e.kind() == io::ErrorKind::PermissionDenied => {
+            let mut permissions = fs::metadata(path)?.permissions();
+
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                permissions.set_mode(permissions.mode() | 0o200); // owner write
+            }
+            #[cfg(not(unix))]
+            permissions.set_readonly(false);
+
+            fs::set_permissions(path, permissions)?;
+            fs::write(path, data)
+        }
+        result => result,
+    }
+}
+
+/// Sets the permissions of `path` to `mode`, does nothing if
+/// `mode` is `None` or when not on Unix.
+fn set_file_mode(path: &Path, mode: Option<u32>) -> Result<(), io::Error> {
+    #[cfg(unix)]
+    if let Some(mode) = mode {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+    }
+    #[cfg(not(unix))]
+    let _ = (path, mode);
+
+    Ok(())
+}
+
+/// Hard-links `src` to `dest` if `link` is true, copies it if linking
+/// is not possible or `link` is false.
+fn link_or_copy(src: &Path, dest: &Path, link: bool) -> Result<(), io::Error> {
+    if link && fs::hard_link(src, dest).is_ok() {
+        return Ok(());
+    }
+
+    fs::copy(src, dest).map(|_| ())
+}
+
+/// Returns `path` if nothing exists there yet, otherwise appends
+/// a number to the file stem until the path is free.
+///
+/// The path is reserved by creating an empty file there, so parallel
+/// callers never get the same path.
+fn reserve_unique_path(path: &Path) -> Result<PathBuf, io::Error> {
+    let mut new_path = path.to_path_buf();
+    let mut n = 1;
+    loop {
+        match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&new_path)
+        {
+            Ok(_) => return Ok(new_path),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                new_path = numbered_path(path, n);
+                n += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Appends `_<n>` to the file stem of `path` (eg. actor1.png -> actor1_2.png).
+fn numbered_path(path: &Path, n: usize) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = path
+        .extension()
+        .map(|ext| format!(".{}", ext.to_string_lossy()))
+        .unwrap_or_default();
+
+    path.with_file_name(format!("{}_{}{}", stem, n, ext))
+}
+
+/// Joins `rel_path` onto `base`, matching its directory components case-insensitively
+/// (eg. `WWW/data`), as some games are extracted from case-insensitive file systems.
+///
+/// Exact matches are preferred. Returns `None` if a directory can't be found.
+fn find_dirs_case_insensitive(base: &Path, rel_path: &Path) -> Option<PathBuf> {
+    let mut path = base.to_path_buf();
+    let mut components = rel_path.iter().peekable();
+
+    while let Some(component) = components.next() {
+        if components.peek().is_none() || path.join(component).is_dir() {
+            path.push(component);
+            continue;
+        }
+
+        let dir = fs::read_dir(&path)
+            .ok()?
+            .filter_map(Result::ok)
+            .find(|entry| {
+                entry.file_name().eq_ignore_ascii_case(component)
+                    && entry.file_type().is_ok_and(|t| t.is_dir())
+            })?;
+        path.push(dir.file_name());
+    }
+
+    Some(path)
+}
+
+/// Like `fs::create_dir_all`, but tolerates other threads
+/// creating (parts of) the same tree at the same time.
+fn create_dir_all(path: &Path) -> Result<(), io::Error> {
+    // a concurrent creation of one of the parents can make the first attempt fail
+    let result = fs::create_dir_all(path).or_else(|_| fs::create_dir_all(path));
+
+    match result {
+        Err(_) if path.is_dir() => Ok(()),
+        result => result,
+    }
+}
+
+fn lowercase_file_name(path: &Path) -> PathBuf {
+    match path.file_name() {
+        Some(name) => path.with_file_name(name.to_string_lossy().to_lowercase()),
+        None => path.to_path_buf(),
     }
 }
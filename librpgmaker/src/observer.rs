@@ -0,0 +1,93 @@
+use std::{path::Path, time::Instant};
+
+use crate::error::Error;
+
+/// Gets notified about the individual steps of `RpgGame::decrypt_all_with()`.
+///
+/// All methods do nothing by default, so only the events of
+/// interest need to be implemented. Files are decrypted in parallel,
+/// calls for different files may therefore arrive interleaved.
+pub trait DecryptObserver: Send {
+    /// Called before `path` is read.
+    fn on_file_start(&mut self, _path: &Path) {}
+
+    /// Called after the decrypted data of `path` was written to `new_path`.
+    fn on_file_done(&mut self, _path: &Path, _new_path: &Path, _bytes: u64) {}
+
+    /// Called when `path` was skipped because its decrypted version exists.
+    fn on_file_skipped(&mut self, _path: &Path) {}
+
+    /// Called when processing `path` failed.
+    fn on_file_error(&mut self, _path: &Path, _err: &Error) {}
+}
+
+/// Observes nothing.
+impl DecryptObserver for () {}
+
+/// Prints every decrypted file to stdout, used when the game is verbose.
+pub(crate) struct ProgressPrinter {
+    pub(crate) num_files: Option<usize>,
+    pub(crate) num_decrypted: u64,
+}
+
+impl ProgressPrinter {
+    fn print(&mut self, path: &Path, result: &str) {
+        self.num_decrypted += 1;
+
+        match self.num_files {
+            Some(num_files) => println!(
+                "[{}/{}] {}\n  -> {}",
+                self.num_decrypted,
+                num_files,
+                path.display(),
+                result
+            ),
+            None => println!(
+                "[{}] {}\n  -> {}",
+                self.num_decrypted,
+                path.display(),
+                result
+            ),
+        }
+    }
+}
+
+impl DecryptObserver for ProgressPrinter {
+    fn on_file_done(&mut self, path: &Path, new_path: &Path, _bytes: u64) {
+        self.print(path, &new_path.display().to_string());
+    }
+
+    fn on_file_skipped(&mut self, path: &Path) {
+        self.print(path, "skipped, already decrypted");
+    }
+}
+
+/// Passes everything on to `inner` while adding up the written bytes and
+/// skipped files, and remembering when the first file was started.
+pub(crate) struct Throughput<'a> {
+    pub(crate) inner: &'a mut dyn DecryptObserver,
+    pub(crate) started: Option<Instant>,
+    pub(crate) bytes: u64,
+    pub(crate) skipped: usize,
+}
+
+impl DecryptObserver for Throughput<'_> {
+    fn on_file_start(&mut self, path: &Path) {
+        self.started.get_or_insert_with(Instant::now);
+        self.inner.on_file_start(path);
+    }
+
+    fn on_file_done(&mut self, path: &Path, new_path: &Path, bytes: u64) {
+        self.bytes += bytes;
+        self.inner.on_file_done(path, new_path, bytes);
+    }
+
+    fn on_file_skipped(&mut self, path: &Path) {
+        self.skipped += 1;
+        self.inner.on_file_skipped(path);
+    }
+
+    fn on_file_error(&mut self, path: &Path, err: &Error) {
+        self.inner.on_file_error(path, err);
+    }
+}
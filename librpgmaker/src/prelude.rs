@@ -1,6 +1,28 @@
 //! re-exports commonly used items to easy inclusion using `use prelude::*;`
 
+pub use crate::error::Error;
+pub use crate::error::ErrorInfo;
+pub use crate::error::ErrorKind;
+pub use crate::find_games;
+pub use crate::key::Key;
+pub use crate::key::KeyEncoding;
+pub use crate::key::KeySource;
+pub use crate::observer::DecryptObserver;
+pub use crate::rpg_file::decrypted_path_of;
+pub use crate::rpg_file::encrypted_path_of;
+pub use crate::rpg_file::find_working_key;
+pub use crate::rpg_file::supported_decrypted_extensions;
+pub use crate::rpg_file::supported_encrypted_extensions;
+pub use crate::rpg_file::RpgEngine;
+pub use crate::rpg_file::RpgEntry;
+pub use crate::rpg_file::RpgFile;
 pub use crate::rpg_file::RpgFileType;
+pub use crate::rpg_file::ScanIndex;
+pub use crate::rpg_file::SignatureMatch;
+pub use crate::system_json::SystemJson;
+pub use crate::DecryptReport;
+pub use crate::GameSummary;
 pub use crate::OutputSettings;
 pub use crate::RpgGame;
 pub use crate::RpgKey;
+pub use crate::TransformFn;
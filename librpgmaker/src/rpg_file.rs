@@ -1,9 +1,98 @@
 use std::{
     fs,
+    io::Read,
     path::{Path, PathBuf},
+    time::SystemTime,
 };
 
-use crate::error::Error;
+use crate::{create_dir_all, error::Error, key::Key};
+
+/// The signature RpgMaker puts in front of every encrypted file.
+const RPG_HEADER: &[u8] = &[
+    0x52, 0x50, 0x47, 0x4D, 0x56, 0x00, 0x00, 0x00, 0x00, 0x03, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+
+/// How much of the RpgMaker signature has to match to consider data encrypted,
+/// see `RpgFile::has_rpg_header_with()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum SignatureMatch {
+    /// All 16 bytes, including the version bytes after `RPGMV`.
+    Full,
+
+    /// Only `RPGMV`, as some games use other version bytes.
+    #[default]
+    Prefix,
+}
+
+/// All extensions of encrypted files, see `supported_encrypted_extensions()`.
+const ENCRYPTED_EXTENSIONS: &[&str] = &["rpgmvo", "ogg_", "rpgmvm", "m4a_", "rpgmvp", "png_"];
+
+/// All extensions of decrypted files, see `supported_decrypted_extensions()`.
+const DECRYPTED_EXTENSIONS: &[&str] = &["ogg", "m4a", "png"];
+
+/// Returns every extension `RpgFileType::scan()` recognizes,
+/// both the MV (eg. `rpgmvp`) and MZ (eg. `png_`) ones.
+///
+/// ## Example
+/// ```
+/// use librpgmaker::prelude::*;
+///
+/// assert!(supported_encrypted_extensions().contains(&"rpgmvp"));
+/// ```
+#[must_use]
+pub fn supported_encrypted_extensions() -> &'static [&'static str] {
+    ENCRYPTED_EXTENSIONS
+}
+
+/// Returns every extension `RpgFileType::to_extension()` produces.
+#[must_use]
+pub fn supported_decrypted_extensions() -> &'static [&'static str] {
+    DECRYPTED_EXTENSIONS
+}
+
+/// Returns the path `encrypted` is decrypted to, or `None`
+/// if its extension is not a known encrypted extension.
+///
+/// Unlike `RpgFile::try_from()`, this does not read the file.
+///
+/// ## Example
+/// ```
+/// use std::path::{Path, PathBuf};
+/// use librpgmaker::prelude::*;
+///
+/// let path = decrypted_path_of(Path::new("img/actor1.rpgmvp"));
+///
+/// assert_eq!(path, Some(PathBuf::from("img/actor1.png")));
+/// ```
+#[must_use]
+pub fn decrypted_path_of(encrypted: &Path) -> Option<PathBuf> {
+    let file_type = RpgFileType::scan(encrypted)?;
+    Some(encrypted.with_extension(file_type.to_extension()))
+}
+
+/// Returns the path `decrypted` would have when encrypted by `engine`,
+/// or `None` if its extension is not a known decrypted extension.
+#[must_use]
+pub fn encrypted_path_of(decrypted: &Path, engine: RpgEngine) -> Option<PathBuf> {
+    let file_type = RpgFileType::scan_decrypted(decrypted)?;
+
+    let ext = match engine {
+        RpgEngine::Mv => file_type.encrypted_extensions()[0],
+        RpgEngine::Mz => file_type.encrypted_extensions()[1],
+    };
+    Some(decrypted.with_extension(ext))
+}
+
+/// The RpgMaker version that encrypted a file, which determines its extension.
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RpgEngine {
+    /// eg. actor1.rpgmvp
+    Mv,
+
+    /// eg. actor1.png_
+    Mz,
+}
 
 /// Represents a decryptable file in an RpgMaker game.
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -26,8 +115,66 @@ pub struct RpgFile {
     pub orig_path: PathBuf,
 }
 
+/// A decryptable file found in the game directory, without its contents.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RpgEntry {
+    pub path: PathBuf,
+    pub file_type: RpgFileType,
+    pub size: u64,
+    pub modified: SystemTime,
+}
+
+/// The decryptable files of a game, collected by a single walk.
+/// See `RpgGame::scan_index()`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ScanIndex {
+    pub entries: Vec<RpgEntry>,
+}
+
+impl ScanIndex {
+    /// Returns the number of files of the given type.
+    #[must_use]
+    pub fn count(&self, file_type: &RpgFileType) -> usize {
+        self.entries
+            .iter()
+            .filter(|entry| &entry.file_type == file_type)
+            .count()
+    }
+
+    /// Returns the combined size of all files in bytes.
+    #[must_use]
+    pub fn total_size(&self) -> u64 {
+        self.entries.iter().map(|entry| entry.size).sum()
+    }
+
+    /// Returns the combined size of all files of the given type in bytes.
+    #[must_use]
+    pub fn size_of(&self, file_type: &RpgFileType) -> u64 {
+        self.entries
+            .iter()
+            .filter(|entry| &entry.file_type == file_type)
+            .map(|entry| entry.size)
+            .sum()
+    }
+}
+
+/// The first bytes of every PNG file.
+const PNG_SIGNATURE: &[u8] = &[0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A];
+
+/// The first 16 bytes of every PNG file: the signature
+/// followed by the length and type of the IHDR chunk.
+const PNG_HEADER: &[u8] = &[
+    0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A, 0x00, 0x00, 0x00, 0x0D, 0x49, 0x48, 0x44, 0x52,
+];
+
+/// Checks for a WebP file, which starts with `RIFF<size>WEBP`.
+/// Some MZ games ship WebP images under the PNG extensions.
+fn is_webp(data: &[u8]) -> bool {
+    data.starts_with(b"RIFF") && data.get(8..12) == Some(b"WEBP")
+}
+
 impl RpgFileType {
-    /// Checks if a given path is an `RpgFile` (based on extension)
+    /// Checks if a given path is an `RpgFile` (based on extension, ignoring case)
     ///
     /// ## Example
     /// ```
@@ -42,14 +189,87 @@ impl RpgFileType {
     /// ```
     #[must_use]
     pub fn scan(path: &Path) -> Option<Self> {
-        let ext = path.extension()?.to_str()?;
-        let ext = match ext {
-            "rpgmvo" | "ogg_" => RpgFileType::Audio,
-            "rpgmvm" | "m4a_" => RpgFileType::Video,
-            "rpgmvp" | "png_" => RpgFileType::Image,
-            _ => return None,
-        };
-        Some(ext)
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        Self::all()
+            .into_iter()
+            .find(|file_type| file_type.encrypted_extensions().contains(&ext.as_str()))
+    }
+
+    /// Like `scan()`, but checks for the extension of a decrypted file
+    /// (see `to_extension()`).
+    #[must_use]
+    pub fn scan_decrypted(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        Self::all()
+            .into_iter()
+            .find(|file_type| file_type.to_extension() == ext)
+    }
+
+    /// Returns the extensions of encrypted files of this type, the MV one first.
+    #[must_use]
+    pub fn encrypted_extensions(&self) -> &'static [&'static str] {
+        match self {
+            RpgFileType::Audio => &ENCRYPTED_EXTENSIONS[0..2],
+            RpgFileType::Video => &ENCRYPTED_EXTENSIONS[2..4],
+            RpgFileType::Image => &ENCRYPTED_EXTENSIONS[4..6],
+        }
+    }
+
+    /// Returns all file types.
+    ///
+    /// ## Example
+    /// ```
+    /// use librpgmaker::prelude::*;
+    ///
+    /// assert!(RpgFileType::all().contains(&RpgFileType::Audio));
+    /// ```
+    #[must_use]
+    pub fn all() -> [RpgFileType; 3] {
+        [RpgFileType::Image, RpgFileType::Audio, RpgFileType::Video]
+    }
+
+    /// Returns a human readable name of the file type.
+    ///
+    /// ## Example
+    /// ```
+    /// use librpgmaker::prelude::*;
+    ///
+    /// assert_eq!(RpgFileType::Image.name(), "image");
+    /// ```
+    #[must_use]
+    pub fn name(&self) -> &'static str {
+        match self {
+            RpgFileType::Audio => "audio",
+            RpgFileType::Video => "video",
+            RpgFileType::Image => "image",
+        }
+    }
+
+    /// Checks if this is `RpgFileType::Image`.
+    #[must_use]
+    pub fn is_image(&self) -> bool {
+        matches!(self, RpgFileType::Image)
+    }
+
+    /// Checks if this is `RpgFileType::Audio`.
+    #[must_use]
+    pub fn is_audio(&self) -> bool {
+        matches!(self, RpgFileType::Audio)
+    }
+
+    /// Checks if this is `RpgFileType::Video`.
+    #[must_use]
+    pub fn is_video(&self) -> bool {
+        matches!(self, RpgFileType::Video)
+    }
+
+    /// Checks if `data` starts with the magic bytes of this file type.
+    pub(crate) fn has_magic(&self, data: &[u8]) -> bool {
+        match self {
+            RpgFileType::Audio => data.starts_with(b"OggS"),
+            RpgFileType::Video => data.get(4..8) == Some(b"ftyp"),
+            RpgFileType::Image => data.starts_with(PNG_SIGNATURE) || is_webp(data),
+        }
     }
 
     /// Returns a "decrypted" file extension
@@ -67,35 +287,47 @@ impl RpgFileType {
     #[must_use]
     pub fn to_extension(&self) -> String {
         match self {
-            RpgFileType::Audio => "ogg",
-            RpgFileType::Video => "m4a",
-            RpgFileType::Image => "png",
+            RpgFileType::Audio => DECRYPTED_EXTENSIONS[0],
+            RpgFileType::Video => DECRYPTED_EXTENSIONS[1],
+            RpgFileType::Image => DECRYPTED_EXTENSIONS[2],
         }
         .to_string()
     }
 }
 
 impl RpgFile {
+    /// Reads the file at `path`, returning `None` if it is not a
+    /// decryptable file or could not be read.
     pub fn from_path(path: &Path) -> Option<Self> {
-        let file_type = RpgFileType::scan(path)?;
-
-        let Ok(data) = fs::read(path) else {
-            return None;
-        };
+        Self::try_from(path).ok()
+    }
 
-        let ext = file_type.to_extension();
+    /// Reads an `RpgFile` of the given type from `reader`, eg. a socket or an entry of an archive.
+    ///
+    /// The stream is read to the end and buffered in memory as a whole,
+    /// so it should not be endless. As there is no real path, the paths are set
+    /// to `stream.<ext>` with the extensions belonging to `file_type`.
+    pub fn from_reader<R: Read>(mut reader: R, file_type: RpgFileType) -> Result<Self, Error> {
+        let mut data = vec![];
+        reader.read_to_end(&mut data)?;
 
-        let mut new_path = path.to_path_buf();
-        let _ = new_path.set_extension(ext);
+        let orig_path = PathBuf::from("stream").with_extension(file_type.encrypted_extensions()[0]);
+        let new_path = orig_path.with_extension(file_type.to_extension());
 
-        Some(Self {
+        Ok(Self {
             data,
             file_type,
             new_path,
-            orig_path: path.to_path_buf(),
+            orig_path,
         })
     }
 
+    /// Creates an `RpgFile` from raw parts without reading anything from disk.
+    ///
+    /// # Safety
+    ///
+    /// This is not memory unsafe, but nothing is checked: `data` is
+    /// trusted to belong to a file of `file_type` located at `orig_path`.
     #[allow(unused)]
     pub unsafe fn from_parts(data: Vec<u8>, file_type: RpgFileType, orig_path: PathBuf) -> Self {
         let mut new_path = orig_path.clone();
@@ -109,6 +341,197 @@ impl RpgFile {
         }
     }
 
+    /// Checks if the data starts with the RpgMaker signature,
+    /// which means the file is (still) encrypted.
+    ///
+    /// Only `RPGMV` has to match, see `has_rpg_header_with()`.
+    #[must_use]
+    pub fn has_rpg_header(&self) -> bool {
+        self.has_rpg_header_with(SignatureMatch::default())
+    }
+
+    /// Same as `has_rpg_header()`, with the given strictness.
+    ///
+    /// Data shorter than the full 16 byte header never has a header.
+    ///
+    /// ## Example
+    /// ```
+    /// use std::path::PathBuf;
+    /// use librpgmaker::prelude::*;
+    ///
+    /// let data = b"RPGMV\0\0\0\0\x04\x01\0\0\0\0\0".to_vec();
+    /// let file = unsafe { RpgFile::from_parts(data, RpgFileType::Image, PathBuf::from("a.png_")) };
+    ///
+    /// assert!(file.has_rpg_header_with(SignatureMatch::Prefix));
+    /// assert!(!file.has_rpg_header_with(SignatureMatch::Full));
+    /// ```
+    #[must_use]
+    pub fn has_rpg_header_with(&self, signature: SignatureMatch) -> bool {
+        match signature {
+            SignatureMatch::Full => self.data.starts_with(RPG_HEADER),
+            SignatureMatch::Prefix => {
+                self.data.len() >= RPG_HEADER.len() && self.data.starts_with(&RPG_HEADER[..5])
+            }
+        }
+    }
+
+    /// Checks if the (decrypted) data starts with the magic bytes
+    /// expected for the file's type, eg. the PNG signature for images.
+    #[must_use]
+    pub fn verify_decrypted(&self) -> bool {
+        self.file_type.has_magic(&self.data)
+    }
+
+    /// Guesses the extension of the (decrypted) data from its magic bytes.
+    ///
+    /// This can differ from `file_type.to_extension()`, eg. audio files
+    /// can also be AAC in an mp4 container instead of ogg, and images WebP instead of PNG.
+    /// Returns `None` if the format is not recognized.
+    #[must_use]
+    pub fn sniff_extension(&self) -> Option<&'static str> {
+        let data = &self.data;
+
+        if data.starts_with(PNG_SIGNATURE) {
+            Some("png")
+        } else if is_webp(data) {
+            Some("webp")
+        } else if data.starts_with(b"OggS") {
+            Some("ogg")
+        } else if data.starts_with(&[0x1A, 0x45, 0xDF, 0xA3]) {
+            Some("webm")
+        } else if data.get(4..8) == Some(b"ftyp") {
+            match data.get(8..12) {
+                Some(b"M4A ") => Some("m4a"),
+                _ => Some("mp4"),
+            }
+        } else {
+            None
+        }
+    }
+
+    /// Decrypts only the 16 header bytes of the (encrypted) file with `key`,
+    /// without copying the rest of the data or modifying the file.
+    ///
+    /// Fails with `Error::FileTooShort` if the file has no full encrypted header.
+    pub fn decrypt_header(&self, key: &Key) -> Result<[u8; 16], Error> {
+        let Some(encrypted_header) = self.data.get(16..32) else {
+            return Err(Error::FileTooShort(self.orig_path.clone()));
+        };
+
+        let mut header = [0; 16];
+        header.copy_from_slice(encrypted_header);
+        xor_header(&mut header, key);
+        Ok(header)
+    }
+
+    /// Checks if decrypting the (encrypted) header with `key`
+    /// results in the magic bytes expected for the file's type.
+    pub(crate) fn decrypts_with(&self, key: &Key) -> bool {
+        self.decrypt_header(key)
+            .is_ok_and(|header| self.file_type.has_magic(&header))
+    }
+
+    /// Returns the length of the file's data when encrypted,
+    /// whether it currently is encrypted or not.
+    #[must_use]
+    pub fn encrypted_len(&self) -> usize {
+        match self.has_rpg_header() {
+            true => self.data.len(),
+            false => self.data.len() + RPG_HEADER.len(),
+        }
+    }
+
+    /// Returns the length of the file's data when decrypted,
+    /// whether it currently is decrypted or not.
+    ///
+    /// Encrypted files that consist of nothing but the header have a length of 0.
+    #[must_use]
+    pub fn decrypted_len(&self) -> usize {
+        match self.has_rpg_header() {
+            true => self.data.len() - RPG_HEADER.len(),
+            false => self.data.len(),
+        }
+    }
+
+    /// Returns the data after the header region, which is never
+    /// touched by encryption.
+    ///
+    /// - encrypted: skips the RpgMaker header and the encrypted header (`data[32..]`)
+    /// - decrypted: skips the plain header (`data[16..]`)
+    ///
+    /// Returns an empty slice if the file is shorter than the header region.
+    #[must_use]
+    pub fn payload(&self) -> &[u8] {
+        let offset = if self.has_rpg_header() { 32 } else { 16 };
+        self.data.get(offset..).unwrap_or(&[])
+    }
+
+    /// Returns the bytes after the end of the file's container, eg. after
+    /// the IEND chunk of a PNG or the last page of an Ogg file.
+    ///
+    /// Works on both encrypted and decrypted data. Returns `None` if there is
+    /// no trailing data, the container could not be parsed or the file type
+    /// is not supported (video).
+    #[must_use]
+    pub fn trailer_hint(&self) -> Option<&[u8]> {
+        let data = match self.has_rpg_header() {
+            true => self.data.get(16..)?,
+            false => &self.data,
+        };
+
+        let end = match self.file_type {
+            RpgFileType::Image => png_end(data)?,
+            RpgFileType::Audio => ogg_end(data)?,
+            RpgFileType::Video => return None,
+        };
+
+        match &data[end..] {
+            [] => None,
+            trailer => Some(trailer),
+        }
+    }
+
+    /// Encodes the (decrypted) data as a `data:` URI, eg. `data:image/png;base64,...`,
+    /// for embedding it in HTML or JSON.
+    ///
+    /// The MIME type is derived from the contents (see `sniff_extension()`),
+    /// falling back to the default for `file_type`. Fails with `Error::AlreadyEncrypted`
+    /// if the file was not decrypted yet.
+    pub fn to_data_uri(&self) -> Result<String, Error> {
+        if self.has_rpg_header() {
+            return Err(Error::AlreadyEncrypted(self.orig_path.clone()));
+        }
+
+        let is_audio = self.file_type == RpgFileType::Audio;
+        let mime = match (self.sniff_extension(), &self.file_type) {
+            (Some("png"), _) => "image/png",
+            (Some("webp"), _) => "image/webp",
+            (Some("ogg"), RpgFileType::Video) => "video/ogg",
+            (Some("ogg"), _) => "audio/ogg",
+            (Some("webm"), _) if is_audio => "audio/webm",
+            (Some("webm"), _) => "video/webm",
+            (Some("m4a"), _) => "audio/mp4",
+            (Some("mp4"), _) if is_audio => "audio/mp4",
+            (Some("mp4"), _) => "video/mp4",
+            (_, RpgFileType::Image) => "image/png",
+            (_, RpgFileType::Audio) => "audio/ogg",
+            (_, RpgFileType::Video) => "video/mp4",
+        };
+        Ok(format!(
+            "data:{};base64,{}",
+            mime,
+            encode_base64(&self.data)
+        ))
+    }
+
+    /// Converts the file's data into a cheaply cloneable `Bytes` buffer
+    /// without copying it.
+    #[cfg(feature = "bytes")]
+    #[must_use]
+    pub fn into_bytes(self) -> bytes::Bytes {
+        bytes::Bytes::from(self.data)
+    }
+
     /// Decrypts the data in the file.
     ///
     /// File before decryption:
@@ -122,17 +545,227 @@ impl RpgFile {
     /// File after decryption:
     ///
     /// | *header (16 bytes)* | *rest of the data* |
-    pub fn decrypt(&mut self, key: &[u8]) -> Result<(), Error> {
+    pub fn decrypt(&mut self, key: &Key) -> Result<(), Error> {
+        if self.data.len() <= 32 {
+            return Err(Error::FileTooShort(self.orig_path.clone()));
+        }
+
+        self.data.drain(..16); // strip off rpgmaker header
+        xor_header(&mut self.data, key);
+        Ok(())
+    }
+
+    /// Returns the decrypted data without modifying the file,
+    /// so the encrypted data stays available.
+    ///
+    /// See `decrypt()` for details on the format.
+    pub fn to_decrypted(&self, key: &Key) -> Result<Vec<u8>, Error> {
         if self.data.len() <= 32 {
             return Err(Error::FileTooShort(self.orig_path.clone()));
         }
 
-        self.data.drain(0..16); // strip off rpgmaker header
-        let (header, _) = self.data.split_at_mut(16); // get a reference to header
-        header
-            .iter_mut()
-            .enumerate()
-            .for_each(|(i, d)| *d ^= key[i % key.len()]); // XOR the header with the key
+        let mut data = self.data[16..].to_vec(); // strip off rpgmaker header
+        xor_header(&mut data, key);
+        Ok(data)
+    }
+
+    /// Decrypts an encrypted image without the key by replacing its
+    /// encrypted header with the header every PNG file starts with.
+    ///
+    /// Fails with `Error::UnexpectedContent` for audio and video files, which
+    /// can't be restored this way, and with `Error::FileNotEncrypted` if the
+    /// data is not encrypted. Images that are not PNGs (eg. WebP) end up broken.
+    pub fn restore_image_header(&mut self) -> Result<(), Error> {
+        if !self.file_type.is_image() {
+            return Err(Error::UnexpectedContent {
+                expected: RpgFileType::Image,
+                path: self.orig_path.clone(),
+            });
+        }
+        if !self.has_rpg_header() {
+            return Err(Error::FileNotEncrypted(self.orig_path.clone()));
+        }
+        if self.data.len() <= 32 {
+            return Err(Error::FileTooShort(self.orig_path.clone()));
+        }
+
+        self.data.drain(..16); // strip off rpgmaker header
+        self.data[..16].copy_from_slice(PNG_HEADER);
+        Ok(())
+    }
+
+    /// Writes the (encrypted) data unchanged to `encrypted_dest` and the data
+    /// decrypted with `key` to `decrypted_dest`, creating missing parent directories.
+    ///
+    /// The file itself is not modified. Fails with `Error::FileNotEncrypted`
+    /// if the data is not encrypted.
+    pub fn write_both(
+        &self,
+        key: &Key,
+        encrypted_dest: &Path,
+        decrypted_dest: &Path,
+    ) -> Result<(), Error> {
+        if !self.has_rpg_header() {
+            return Err(Error::FileNotEncrypted(self.orig_path.clone()));
+        }
+        let decrypted = self.to_decrypted(key)?;
+
+        for (dest, data) in [(encrypted_dest, &self.data), (decrypted_dest, &decrypted)] {
+            if let Some(parent) = dest.parent() {
+                create_dir_all(parent)?;
+            }
+            fs::write(dest, data)?;
+        }
+
+        Ok(())
+    }
+
+    /// Encrypts the data in the file, the reverse of `decrypt()`.
+    ///
+    /// Fails if the data already starts with the RpgMaker header, or with
+    /// `Error::FileTooShort` if it is no longer than 16 bytes, which `decrypt()`
+    /// could not read back.
+    /// When `strict` is true, the data must also start with the magic
+    /// bytes belonging to the file's type (eg. the PNG signature for images),
+    /// which catches files labeled with the wrong type.
+    ///
+    /// The paths of the file are left untouched.
+    pub fn encrypt(&mut self, key: &Key, strict: bool) -> Result<(), Error> {
+        if self.has_rpg_header() {
+            return Err(Error::AlreadyEncrypted(self.orig_path.clone()));
+        }
+
+        // `decrypt()` needs more than the header and the 16 encrypted bytes
+        if self.data.len() <= 16 {
+            return Err(Error::FileTooShort(self.orig_path.clone()));
+        }
+
+        if strict && !self.file_type.has_magic(&self.data) {
+            return Err(Error::UnexpectedContent {
+                expected: self.file_type.clone(),
+                path: self.orig_path.clone(),
+            });
+        }
+
+        xor_header(&mut self.data, key);
+        self.data.splice(0..0, RPG_HEADER.iter().copied()); // put the rpgmaker header in front
         Ok(())
     }
 }
+
+/// XORs the first 16 bytes of `data` with the key, which both encrypts and decrypts them.
+fn xor_header(data: &mut [u8], key: &Key) {
+    let key = key.as_bytes();
+    data.iter_mut()
+        .take(16)
+        .enumerate()
+        .for_each(|(i, d)| *d ^= key[i % key.len()]);
+}
+
+impl TryFrom<&Path> for RpgFile {
+    type Error = Error;
+
+    fn try_from(path: &Path) -> Result<Self, Self::Error> {
+        let file_type =
+            RpgFileType::scan(path).ok_or_else(|| Error::NotRpgFile(path.to_path_buf()))?;
+
+        let data = fs::read(path)?;
+
+        let ext = file_type.to_extension();
+
+        let mut new_path = path.to_path_buf();
+        let _ = new_path.set_extension(ext);
+
+        Ok(Self {
+            data,
+            file_type,
+            new_path,
+            orig_path: path.to_path_buf(),
+        })
+    }
+}
+
+impl TryFrom<PathBuf> for RpgFile {
+    type Error = Error;
+
+    fn try_from(path: PathBuf) -> Result<Self, Self::Error> {
+        Self::try_from(path.as_path())
+    }
+}
+
+/// Encodes `data` as standard base64 with padding.
+fn encode_base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let buffer = chunk.iter().enumerate().fold(0u32, |buffer, (i, byte)| {
+            buffer | ((*byte as u32) << (16 - i * 8))
+        });
+
+        // every 3 bytes make 4 digits of 6 bits, missing bytes are padded with `=`
+        for i in 0..4 {
+            match i <= chunk.len() {
+                true => encoded.push(ALPHABET[(buffer >> (18 - i * 6)) as usize & 63] as char),
+                false => encoded.push('='),
+            }
+        }
+    }
+
+    encoded
+}
+
+/// Returns the offset right after the IEND chunk of a PNG.
+///
+/// The signature and the IHDR chunk, which always take up the first 33 bytes,
+/// are skipped without reading them since they may still be encrypted.
+fn png_end(data: &[u8]) -> Option<usize> {
+    let mut offset = 33;
+    loop {
+        let len = u32::from_be_bytes(data.get(offset..offset + 4)?.try_into().ok()?) as usize;
+        let chunk_type = data.get(offset + 4..offset + 8)?;
+
+        // length, type, data and crc
+        offset = offset.checked_add(len + 12)?;
+        if offset > data.len() {
+            return None;
+        }
+        if chunk_type == b"IEND" {
+            return Some(offset);
+        }
+    }
+}
+
+/// Returns the offset right after the last Ogg page.
+///
+/// The page size is stored after the first 16 bytes,
+/// so this works even if the first page's header is encrypted.
+fn ogg_end(data: &[u8]) -> Option<usize> {
+    let mut offset = 0;
+    loop {
+        let page = &data[offset..];
+        if page.is_empty() || (offset > 0 && !page.starts_with(b"OggS")) {
+            return Some(offset);
+        }
+
+        let segments = *page.get(26)? as usize;
+        let table = page.get(27..27 + segments)?;
+        let size = 27 + segments + table.iter().map(|&len| len as usize).sum::<usize>();
+        if size > page.len() {
+            return None;
+        }
+        offset += size;
+    }
+}
+
+/// Tries each of the `candidates` on the header of an encrypted `sample`
+/// and returns the first key that decrypts it to a valid file.
+///
+/// Only the header is decrypted, `sample` is left untouched.
+#[must_use]
+pub fn find_working_key(sample: &RpgFile, candidates: &[Key]) -> Option<Key> {
+    candidates
+        .iter()
+        .find(|key| sample.decrypts_with(key))
+        .cloned()
+}
@@ -0,0 +1,56 @@
+use std::path::Path;
+
+use crate::error::Error;
+
+/// Name of the file in the game root that lists paths to leave untouched.
+#[cfg(feature = "ignore")]
+const RRDIGNORE_FILE: &str = ".rrdignore";
+
+/// The gitignore-style rules of a game's `.rrdignore` file.
+///
+/// Without the `ignore` feature the file is not read and nothing is ignored.
+#[derive(Debug, Default)]
+pub(crate) struct RrdIgnore {
+    #[cfg(feature = "ignore")]
+    matcher: Option<ignore::gitignore::Gitignore>,
+}
+
+impl RrdIgnore {
+    /// Reads the `.rrdignore` file in `game_path`, if there is one.
+    #[cfg(feature = "ignore")]
+    pub(crate) fn load(game_path: &Path) -> Result<Self, Error> {
+        let path = game_path.join(RRDIGNORE_FILE);
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+
+        let (matcher, err) = ignore::gitignore::Gitignore::new(path);
+        if let Some(err) = err {
+            return Err(err.into());
+        }
+
+        Ok(Self {
+            matcher: Some(matcher),
+        })
+    }
+
+    #[cfg(not(feature = "ignore"))]
+    pub(crate) fn load(_game_path: &Path) -> Result<Self, Error> {
+        Ok(Self::default())
+    }
+
+    /// Checks if the file at `path`, or any of its parent directories, is ignored.
+    /// `path` has to be inside the game directory.
+    #[cfg(feature = "ignore")]
+    pub(crate) fn is_ignored(&self, path: &Path) -> bool {
+        match &self.matcher {
+            Some(matcher) => matcher.matched_path_or_any_parents(path, false).is_ignore(),
+            None => false,
+        }
+    }
+
+    #[cfg(not(feature = "ignore"))]
+    pub(crate) fn is_ignored(&self, _path: &Path) -> bool {
+        false
+    }
+}
@@ -1,17 +1,79 @@
-use std::{fs, path::PathBuf};
+use std::{
+    collections::hash_map::DefaultHasher,
+    fs,
+    hash::{Hash, Hasher},
+    io::Read,
+    path::PathBuf,
+};
 
 use serde_json::Value;
 
-use crate::{error::Error, HAS_ENC_AUIDO_KEY, HAS_ENC_IMG_KEY};
+use crate::{check_encrypted, error::Error, HAS_ENC_AUIDO_KEY, HAS_ENC_IMG_KEY};
 
+pub(crate) const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// The game's System.json file, which holds the encryption key and flags.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct SystemJson {
     pub data: Value,
     pub path: PathBuf,
     pub encrypted: bool,
+
+    checksum: u64,
 }
 
 impl SystemJson {
+    /// Parses System.json from any reader.
+    ///
+    /// `path` is where the file will be written to by `write()`.
+    pub fn from_reader(mut reader: impl Read, path: PathBuf) -> Result<Self, Error> {
+        let mut system = Vec::new();
+        reader.read_to_end(&mut system)?;
+
+        Self::from_slice(&system, path)
+    }
+
+    fn from_slice(system: &[u8], path: PathBuf) -> Result<Self, Error> {
+        // some editors save System.json with a UTF-8 BOM, which serde_json rejects.
+        // the checksum still covers the BOM, as it is compared to the file on disk
+        let json = system.strip_prefix(UTF8_BOM).unwrap_or(system);
+
+        match serde_json::from_slice::<Value>(json) {
+            Ok(v) => Ok(SystemJson {
+                encrypted: check_encrypted(&v)?,
+                data: v,
+                path,
+                checksum: checksum(system),
+            }),
+            Err(e) => Err(Error::SystemJsonInvalidJson(e)),
+        }
+    }
+
+    /// Reads and parses the System.json at `path`.
+    ///
+    /// With the `mmap` feature, the file is memory-mapped and parsed directly
+    /// from the mapping instead of being read into memory first, falling back
+    /// to reading it if it can't be mapped.
+    pub fn from_path(path: PathBuf) -> Result<Self, Error> {
+        #[cfg(feature = "mmap")]
+        if let Ok(file) = fs::File::open(&path) {
+            // SAFETY: the map only lives while parsing. If another program truncates
+            // the file meanwhile, reading the map can fault, which is the accepted
+            // risk of this feature. Any other modification only results in invalid JSON.
+            if let Ok(map) = unsafe { memmap2::Mmap::map(&file) } {
+                return Self::from_slice(&map, path);
+            }
+        }
+
+        Self::from_reader(fs::File::open(&path)?, path)
+    }
+
+    /// Checksum of the file contents as they were last read or written.
+    #[must_use]
+    pub fn checksum(&self) -> u64 {
+        self.checksum
+    }
+
     pub fn set_decrypt(&mut self, encrypted: bool) -> Result<(), Error> {
         let mut set_key = |key: &str| -> Result<(), Error> {
             let json_key = self.data.get_mut(key).ok_or(Error::SystemJsonKeyNotFound {
@@ -29,10 +91,33 @@ impl SystemJson {
         Ok(())
     }
 
+    /// Writes the file back to disk.
+    ///
+    /// Fails with `Error::SystemJsonModifiedExternally` if the file
+    /// on disk was changed since it was read.
     pub fn write(&mut self) -> Result<(), Error> {
+        let on_disk = fs::read(&self.path)?;
+        if checksum(&on_disk) != self.checksum {
+            return Err(Error::SystemJsonModifiedExternally(self.path.clone()));
+        }
+
+        self.force_write()
+    }
+
+    /// Writes the file back to disk, even if it was changed since it was read.
+    pub fn force_write(&mut self) -> Result<(), Error> {
         self.set_decrypt(self.encrypted)?;
 
         let data = self.data.to_string();
-        Ok(fs::write(&self.path, data)?)
+        fs::write(&self.path, &data)?;
+        self.checksum = checksum(data.as_bytes());
+
+        Ok(())
     }
 }
+
+pub fn checksum(data: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    data.hash(&mut hasher);
+    hasher.finish()
+}
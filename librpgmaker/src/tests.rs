@@ -10,8 +10,12 @@ use tempdir::TempDir;
 
 use crate::{
     create_path_from_output,
-    rpg_file::{RpgFile, RpgFileType},
-    OutputSettings,
+    error::{Error, ErrorInfo, ErrorKind},
+    key::{Key, KeySource},
+    observer::DecryptObserver,
+    rpg_file::{find_working_key, RpgEngine, RpgFile, RpgFileType, SignatureMatch},
+    system_json::{self, SystemJson},
+    OutputSettings, RpgGame, ENCKEY_KEYS,
 };
 
 const IMG_ENC: &[u8] = &[
@@ -47,6 +51,27 @@ const KEY: &[u8] = &[
     15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15,
 ];
 
+/// Lays out a minimal encrypted game containing a single image
+/// and returns the path to the game directory.
+fn create_test_game(dir: &Path) -> PathBuf {
+    let game_dir = dir.join("game");
+    fs::create_dir_all(game_dir.join("www/data")).unwrap();
+    fs::create_dir_all(game_dir.join("www/img/pictures")).unwrap();
+
+    fs::write(
+        game_dir.join("www/data/System.json"),
+        r#"{"hasEncryptedAudio":true,"hasEncryptedImages":true,"encryptionKey":"0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f"}"#,
+    )
+    .unwrap();
+    fs::write(game_dir.join("www/img/pictures/test.rpgmvp"), IMG_ENC).unwrap();
+
+    game_dir
+}
+
+fn key() -> Key {
+    Key::from_bytes(KEY.to_vec()).unwrap()
+}
+
 #[test]
 fn test_decrypt() {
     let mut file;
@@ -58,7 +83,7 @@ fn test_decrypt() {
         );
     }
 
-    file.decrypt(KEY).unwrap();
+    file.decrypt(&key()).unwrap();
     let mut hasher = Sha256::new();
     hasher.update(&file.data);
     let result = hasher.finalize();
@@ -78,7 +103,8 @@ fn test_decryption_fail() {
         );
     }
 
-    file.decrypt(&[1, 2, 3, 4, 5]).unwrap();
+    file.decrypt(&Key::from_bytes(vec![1, 2, 3, 4, 5]).unwrap())
+        .unwrap();
     let mut hasher = Sha256::new();
     hasher.update(&file.data);
     let result = hasher.finalize();
@@ -125,6 +151,157 @@ fn test_create_path_from_output_flatten_2() {
     assert_eq!(new_path, PathBuf::from("output_dir/www_img_test.ogg"));
 }
 
+#[test]
+fn test_create_path_from_output_flatten_underscores() {
+    let file1 = unsafe {
+        RpgFile::from_parts(
+            vec![],
+            RpgFileType::Image,
+            PathBuf::from("game/www/img_2/my_pic.rpgmvp"),
+        )
+    };
+    let out1 = OutputSettings::Flatten {
+        dir: "output_dir".into(),
+    };
+
+    let new_path = create_path_from_output(&out1, &file1, Path::new("game")).unwrap();
+
+    assert_eq!(
+        new_path,
+        PathBuf::from("output_dir/www_img%5F2_my%5Fpic.png")
+    );
+}
+
+#[test]
+fn test_create_path_from_output_flatten_no_collision() {
+    let out = OutputSettings::Flatten {
+        dir: "output_dir".into(),
+    };
+    let paths: Vec<_> = ["game/a_/b.rpgmvp", "game/a/_b.rpgmvp", "game/a%5F/b.rpgmvp"]
+        .into_iter()
+        .map(|path| {
+            let file =
+                unsafe { RpgFile::from_parts(vec![], RpgFileType::Image, PathBuf::from(path)) };
+            create_path_from_output(&out, &file, Path::new("game")).unwrap()
+        })
+        .collect();
+
+    assert_eq!(paths[0], PathBuf::from("output_dir/a%5F_b.png"));
+    assert_eq!(paths[1], PathBuf::from("output_dir/a_%5Fb.png"));
+    assert_eq!(paths[2], PathBuf::from("output_dir/a%255F_b.png"));
+}
+
+#[test]
+fn test_create_path_from_output_flatten_mixed_separators() {
+    let file1 = unsafe {
+        RpgFile::from_parts(
+            vec![],
+            RpgFileType::Image,
+            PathBuf::from("game/www/img\\pictures/test.rpgmvp"),
+        )
+    };
+    let out1 = OutputSettings::Flatten {
+        dir: "output_dir".into(),
+    };
+
+    let new_path = create_path_from_output(&out1, &file1, Path::new("game")).unwrap();
+
+    assert_eq!(
+        new_path,
+        PathBuf::from("output_dir/www_img_pictures_test.png")
+    );
+}
+
+#[test]
+fn test_create_path_from_output_by_type() {
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let out = OutputSettings::ByType {
+        dir: tmp_dir.path().join("out"),
+    };
+
+    for (file_type, orig_path, expected) in [
+        (
+            RpgFileType::Image,
+            "game/www/img/faces/actor1.rpgmvp",
+            "img/actor1.png",
+        ),
+        (
+            RpgFileType::Audio,
+            "game/www/audio/bgm/theme.rpgmvo",
+            "audio/theme.ogg",
+        ),
+        (
+            RpgFileType::Video,
+            "game/www/movies/intro.rpgmvm",
+            "video/intro.m4a",
+        ),
+    ] {
+        let file = unsafe { RpgFile::from_parts(vec![], file_type, PathBuf::from(orig_path)) };
+
+        let new_path = create_path_from_output(&out, &file, Path::new("game")).unwrap();
+
+        assert_eq!(new_path, tmp_dir.path().join("out").join(expected));
+    }
+}
+
+#[test]
+fn test_create_path_from_output_by_type_collision() {
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let out = OutputSettings::ByType {
+        dir: tmp_dir.path().join("out"),
+    };
+
+    let file1 = unsafe {
+        RpgFile::from_parts(
+            vec![],
+            RpgFileType::Image,
+            PathBuf::from("game/www/img/faces/actor1.rpgmvp"),
+        )
+    };
+    let file2 = unsafe {
+        RpgFile::from_parts(
+            vec![],
+            RpgFileType::Image,
+            PathBuf::from("game/www/img/pictures/actor1.rpgmvp"),
+        )
+    };
+
+    let path1 = create_path_from_output(&out, &file1, Path::new("game")).unwrap();
+    let path2 = create_path_from_output(&out, &file2, Path::new("game")).unwrap();
+
+    assert_eq!(path1, tmp_dir.path().join("out/img/actor1.png"));
+    assert_eq!(path2, tmp_dir.path().join("out/img/actor1_1.png"));
+}
+
+#[test]
+fn test_create_path_from_output_flatten_depth() {
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let file = unsafe {
+        RpgFile::from_parts(
+            vec![],
+            RpgFileType::Image,
+            PathBuf::from("game/www/img/pictures/sub/x.rpgmvp"),
+        )
+    };
+
+    for (keep_depth, expected) in [
+        (0, "www_img_pictures_sub_x.png"),
+        (1, "www/img_pictures_sub_x.png"),
+        (2, "www/img/pictures_sub_x.png"),
+        (10, "www/img/pictures/sub/x.png"),
+    ] {
+        let out = OutputSettings::FlattenDepth {
+            dir: tmp_dir.path().join("out"),
+            keep_depth,
+        };
+
+        let new_path = create_path_from_output(&out, &file, Path::new("game")).unwrap();
+
+        assert_eq!(new_path, tmp_dir.path().join("out").join(expected));
+        assert!(new_path.parent().unwrap().is_dir());
+    }
+}
+
 #[test]
 fn test_create_path_from_output_replace_1() {
     let tmp_dir = TempDir::new("rrd-test").unwrap();
@@ -143,3 +320,1894 @@ fn test_create_path_from_output_replace_1() {
 
     assert_eq!(new_path, tmp_dir.path().join("files/game/www/img/test.ogg"));
 }
+
+#[test]
+fn test_try_get_key_alternate_field() {
+    let system_json = serde_json::json!({ "encryptKey": "0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f" });
+
+    let (found, orig_key, source, _) = RpgGame::try_get_key(&system_json, ENCKEY_KEYS).unwrap();
+
+    assert_eq!(found, key());
+    assert_eq!(orig_key, "0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f");
+    assert_eq!(source, KeySource::AlternateField("encryptKey".into()));
+}
+
+#[test]
+fn test_with_key_fields() {
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let game_dir = create_test_game(tmp_dir.path());
+    fs::write(
+        game_dir.join("www/data/System.json"),
+        r#"{"hasEncryptedAudio":true,"hasEncryptedImages":true,"pluginKey":"0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f"}"#,
+    )
+    .unwrap();
+
+    assert!(matches!(
+        RpgGame::new(&game_dir, false),
+        Err(Error::NotEncrypted)
+    ));
+
+    let game = RpgGame::with_key_fields(&game_dir, &["encryptionKey", "pluginKey"], false).unwrap();
+    assert_eq!(game.get_key().bytes, key().as_bytes());
+    assert_eq!(
+        game.key_source(),
+        &KeySource::AlternateField("pluginKey".into())
+    );
+}
+
+#[test]
+fn test_payload_unchanged_by_decrypt() {
+    let mut file = unsafe {
+        RpgFile::from_parts(
+            IMG_ENC.to_vec(),
+            RpgFileType::Image,
+            PathBuf::from("test_images/test.rpgmvp"),
+        )
+    };
+
+    assert!(file.has_rpg_header());
+    assert_eq!(file.payload(), &IMG_ENC[32..]);
+
+    file.decrypt(&key()).unwrap();
+
+    assert!(!file.has_rpg_header());
+    assert_eq!(file.payload(), &IMG_ENC[32..]);
+}
+
+#[test]
+fn test_new_game_dir_not_found() {
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let game_dir = tmp_dir.path().join("does_not_exist");
+
+    let result = RpgGame::new(&game_dir, false);
+
+    assert!(matches!(result, Err(Error::GameDirNotFound(path)) if path == game_dir));
+}
+
+#[test]
+fn test_walk() {
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let game_dir = create_test_game(tmp_dir.path());
+
+    let game = RpgGame::new(&game_dir, false).unwrap();
+    let entries = game.walk().unwrap();
+
+    assert_eq!(entries.len(), 1);
+    assert_eq!(
+        entries[0].path,
+        game_dir.join("www/img/pictures/test.rpgmvp")
+    );
+    assert_eq!(entries[0].file_type, RpgFileType::Image);
+    assert_eq!(entries[0].size, IMG_ENC.len() as u64);
+}
+
+#[test]
+fn test_try_from_path() {
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let game_dir = create_test_game(tmp_dir.path());
+
+    let path = game_dir.join("www/img/pictures/test.rpgmvp");
+    let file = RpgFile::try_from(path.as_path()).unwrap();
+    assert_eq!(file.data, IMG_ENC);
+    assert_eq!(file.new_path, game_dir.join("www/img/pictures/test.png"));
+
+    let not_rpg = game_dir.join("www/data/System.json");
+    let result = RpgFile::try_from(not_rpg.clone());
+    assert!(matches!(result, Err(Error::NotRpgFile(path)) if path == not_rpg));
+}
+
+#[test]
+fn test_decrypt_all_no_update_encryption() {
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let game_dir = create_test_game(tmp_dir.path());
+    let system_json_path = game_dir.join("www/data/System.json");
+    let system_json = fs::read_to_string(&system_json_path).unwrap();
+
+    let mut game = RpgGame::new(&game_dir, false).unwrap();
+    game.set_no_update_encryption(true);
+    let results = game.decrypt_all(&OutputSettings::Replace).unwrap();
+
+    assert!(results.iter().all(Result::is_ok));
+    assert!(game_dir.join("www/img/pictures/test.png").exists());
+    assert_eq!(fs::read_to_string(&system_json_path).unwrap(), system_json);
+    assert!(RpgGame::new(&game_dir, false).unwrap().is_encrypted());
+}
+
+#[test]
+fn test_decrypt_all_system_json_modified_externally() {
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let game_dir = create_test_game(tmp_dir.path());
+    let system_json_path = game_dir.join("www/data/System.json");
+
+    let mut game = RpgGame::new(&game_dir, false).unwrap();
+    fs::write(
+        &system_json_path,
+        r#"{"hasEncryptedAudio":true,"hasEncryptedImages":true,"encryptionKey":"0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f","gameTitle":"edited"}"#,
+    )
+    .unwrap();
+
+    let result = game.decrypt_all(&OutputSettings::NextTo);
+
+    assert!(matches!(
+        result,
+        Err(Error::SystemJsonModifiedExternally(path)) if path == system_json_path
+    ));
+}
+
+#[test]
+fn test_decrypt_all_force_system_json_write() {
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let game_dir = create_test_game(tmp_dir.path());
+    let system_json_path = game_dir.join("www/data/System.json");
+
+    let mut game = RpgGame::new(&game_dir, false).unwrap();
+    fs::write(
+        &system_json_path,
+        r#"{"hasEncryptedAudio":true,"hasEncryptedImages":true,"encryptionKey":"0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f","gameTitle":"edited"}"#,
+    )
+    .unwrap();
+
+    game.set_force_system_json_write(true);
+    assert!(game.decrypt_all(&OutputSettings::Replace).is_ok());
+    assert!(!RpgGame::new(&game_dir, false).unwrap().is_encrypted());
+}
+
+#[test]
+fn test_encrypt() {
+    let mut file = unsafe {
+        RpgFile::from_parts(
+            IMG_ENC.to_vec(),
+            RpgFileType::Image,
+            PathBuf::from("test_images/test.rpgmvp"),
+        )
+    };
+
+    let result = file.encrypt(&key(), true);
+    assert!(matches!(result, Err(Error::AlreadyEncrypted(_))));
+
+    file.decrypt(&key()).unwrap();
+    file.encrypt(&key(), true).unwrap();
+    assert!(file.has_rpg_header());
+}
+
+#[test]
+fn test_encrypt_strict_wrong_type() {
+    let mut file = unsafe {
+        RpgFile::from_parts(
+            IMG_ENC.to_vec(),
+            RpgFileType::Image,
+            PathBuf::from("test_images/test.rpgmvp"),
+        )
+    };
+    file.decrypt(&key()).unwrap();
+    file.file_type = RpgFileType::Audio;
+
+    let result = file.clone().encrypt(&key(), true);
+    assert!(matches!(
+        result,
+        Err(Error::UnexpectedContent {
+            expected: RpgFileType::Audio,
+            ..
+        })
+    ));
+
+    file.encrypt(&key(), false).unwrap();
+}
+
+#[test]
+fn test_find_working_key() {
+    let file = unsafe {
+        RpgFile::from_parts(
+            IMG_ENC.to_vec(),
+            RpgFileType::Image,
+            PathBuf::from("test_images/test.rpgmvp"),
+        )
+    };
+
+    let candidates = vec![Key::from_bytes(vec![1, 2, 3, 4, 5]).unwrap(), key()];
+    assert_eq!(find_working_key(&file, &candidates), Some(key()));
+    assert_eq!(find_working_key(&file, &candidates[..1]), None);
+    assert_eq!(file.data, IMG_ENC);
+}
+
+#[test]
+fn test_from_parts() {
+    let system_json = SystemJson::from_reader(
+        r#"{"hasEncryptedAudio":false,"hasEncryptedImages":true}"#.as_bytes(),
+        PathBuf::from("game/data/System.json"),
+    )
+    .unwrap();
+
+    let game = RpgGame::from_parts("game", system_json, key(), false);
+
+    assert!(game.is_encrypted());
+    assert_eq!(game.get_key().bytes, KEY);
+    assert_eq!(game.get_key().string, "0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f");
+    assert_eq!(game.key(), &key());
+}
+
+#[test]
+fn test_system_json_with_bom() {
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let game_dir = create_test_game(tmp_dir.path());
+    let system_json_path = game_dir.join("www/data/System.json");
+    let mut data = b"\xEF\xBB\xBF".to_vec();
+    data.extend(fs::read(&system_json_path).unwrap());
+    fs::write(&system_json_path, data).unwrap();
+
+    let mut game = RpgGame::new(&game_dir, false).unwrap();
+    assert_eq!(game.get_key().bytes, KEY);
+
+    game.set_encrypted(false).unwrap();
+    assert!(!RpgGame::new(&game_dir, false).unwrap().is_encrypted());
+}
+
+#[test]
+fn test_create_path_from_output_concurrent() {
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let game_dir = tmp_dir.path().join("game");
+    let out = OutputSettings::Output {
+        dir: tmp_dir.path().join("output"),
+    };
+
+    std::thread::scope(|scope| {
+        for thread in 0..32 {
+            let (game_dir, out) = (&game_dir, &out);
+            scope.spawn(move || {
+                for i in 0..16 {
+                    let file = unsafe {
+                        RpgFile::from_parts(
+                            vec![],
+                            RpgFileType::Image,
+                            game_dir.join(format!("www/img/{}/{}_{}.rpgmvp", i % 4, thread, i)),
+                        )
+                    };
+
+                    let new_path = create_path_from_output(out, &file, game_dir).unwrap();
+                    fs::write(new_path, "test").unwrap();
+                }
+            });
+        }
+    });
+
+    for i in 0..4 {
+        let dir = tmp_dir.path().join(format!("output/www/img/{}", i));
+        assert_eq!(fs::read_dir(dir).unwrap().count(), 32 * 4);
+    }
+}
+
+#[test]
+fn test_decrypt_all_lowercase_output() {
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let game_dir = create_test_game(tmp_dir.path());
+    fs::rename(
+        game_dir.join("www/img/pictures/test.rpgmvp"),
+        game_dir.join("www/img/pictures/Test.rpgmvp"),
+    )
+    .unwrap();
+
+    let mut game = RpgGame::new(&game_dir, false).unwrap();
+    game.set_lowercase_output(true);
+    let results = game.decrypt_all(&OutputSettings::NextTo).unwrap();
+
+    assert!(results.iter().all(Result::is_ok));
+    let names: Vec<_> = fs::read_dir(game_dir.join("www/img/pictures"))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    assert!(names.contains(&"test.png".into()));
+}
+
+#[test]
+fn test_decrypt_all_lowercase_output_collision() {
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let game_dir = create_test_game(tmp_dir.path());
+    fs::write(game_dir.join("www/img/pictures/TEST.rpgmvp"), IMG_ENC).unwrap();
+
+    let mut game = RpgGame::new(&game_dir, false).unwrap();
+    game.set_lowercase_output(true);
+    let result = game.decrypt_all(&OutputSettings::NextTo);
+
+    assert!(matches!(result, Err(Error::OutputPathCollision(c)) if c.len() == 1));
+    assert!(!game_dir.join("www/img/pictures/test.png").exists());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_decrypt_all_write_index() {
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let game_dir = create_test_game(tmp_dir.path());
+    let output_dir = tmp_dir.path().join("output");
+
+    let mut game = RpgGame::new(&game_dir, false).unwrap();
+    game.set_write_index(true);
+    game.decrypt_all(&OutputSettings::Flatten {
+        dir: output_dir.clone(),
+    })
+    .unwrap();
+
+    let index = fs::read_to_string(output_dir.join("index.json")).unwrap();
+    let index: serde_json::Value = serde_json::from_str(&index).unwrap();
+    assert_eq!(
+        index,
+        serde_json::json!({
+            "www/img/pictures/test.rpgmvp": {
+                "path": "www_img_pictures_test.png",
+                "type": "image",
+            }
+        })
+    );
+}
+
+#[test]
+fn test_decrypt_all_only_referenced() {
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let game_dir = create_test_game(tmp_dir.path());
+    fs::write(game_dir.join("www/img/pictures/unused.rpgmvp"), IMG_ENC).unwrap();
+    fs::write(
+        game_dir.join("www/data/Map001.json"),
+        r#"{"events":[{"pages":[{"image":{"characterName":"test"}}]}]}"#,
+    )
+    .unwrap();
+
+    let mut game = RpgGame::new(&game_dir, false).unwrap();
+    let referenced = game.referenced_assets().unwrap();
+    assert_eq!(
+        referenced,
+        [game_dir.join("www/img/pictures/test.rpgmvp")].into()
+    );
+
+    game.set_only_referenced(true);
+    let results = game.decrypt_all(&OutputSettings::NextTo).unwrap();
+
+    assert_eq!(results.len(), 1);
+    assert!(game_dir.join("www/img/pictures/test.png").exists());
+    assert!(!game_dir.join("www/img/pictures/unused.png").exists());
+}
+
+#[test]
+fn test_referenced_assets_data_files() {
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let game_dir = create_test_game(tmp_dir.path());
+    let map_path = game_dir.join("www/data/Map001.json");
+    fs::write(&map_path, "\u{feff}{\"image\":\"test\"}").unwrap();
+
+    let game = RpgGame::new(&game_dir, false).unwrap();
+    assert_eq!(game.referenced_assets().unwrap().len(), 1);
+
+    fs::write(&map_path, "{").unwrap();
+    assert!(matches!(
+        game.referenced_assets(),
+        Err(Error::DataFileInvalidJson { path, .. }) if path == map_path
+    ));
+}
+
+#[test]
+fn test_key_from_hex() {
+    assert_eq!(
+        Key::from_hex("0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f").unwrap(),
+        key()
+    );
+    assert_eq!(key().to_hex(), "0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f");
+
+    assert!(matches!(Key::from_hex(""), Err(Error::EmptyKey)));
+    assert!(matches!(Key::from_hex("0f0"), Err(Error::InvalidKey(_))));
+    assert!(matches!(Key::from_hex("zz"), Err(Error::KeyParseError(_))));
+    assert!(matches!(Key::try_from(vec![]), Err(Error::EmptyKey)));
+}
+
+#[test]
+fn test_validate_key() {
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let game_dir = create_test_game(tmp_dir.path());
+    let system_json_path = game_dir.join("www/data/System.json");
+
+    let game = RpgGame::new(&game_dir, false).unwrap();
+    game.validate_key().unwrap();
+
+    let system_json =
+        SystemJson::from_reader(fs::File::open(&system_json_path).unwrap(), system_json_path)
+            .unwrap();
+    let wrong_key = Key::from_bytes(vec![1, 2, 3, 4, 5]).unwrap();
+    let game = RpgGame::from_parts(&game_dir, system_json, wrong_key, false);
+    assert!(matches!(game.validate_key(), Err(Error::KeyMismatch(_))));
+}
+
+#[test]
+fn test_create_path_from_output_in_place_suffixed() {
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let game_dir = create_test_game(tmp_dir.path());
+    let orig_file = game_dir.join("www/img/pictures/test.rpgmvp");
+
+    let file = unsafe { RpgFile::from_parts(vec![], RpgFileType::Image, orig_file.clone()) };
+    let out = OutputSettings::InPlaceSuffixed {
+        suffix: ".decrypted".into(),
+    };
+
+    let new_path = create_path_from_output(&out, &file, &game_dir).unwrap();
+    assert_eq!(
+        new_path,
+        game_dir.join("www/img/pictures/test.decrypted.png")
+    );
+
+    fs::write(&new_path, "test").unwrap();
+    let new_path = create_path_from_output(&out, &file, &game_dir).unwrap();
+    assert_eq!(
+        new_path,
+        game_dir.join("www/img/pictures/test.decrypted_1.png")
+    );
+    assert!(orig_file.exists());
+}
+
+#[test]
+fn test_decrypt_all_clear_readonly() {
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let game_dir = create_test_game(tmp_dir.path());
+    let decrypted = game_dir.join("www/img/pictures/test.png");
+
+    fs::write(&decrypted, "old").unwrap();
+    let mut permissions = fs::metadata(&decrypted).unwrap().permissions();
+    permissions.set_readonly(true);
+    fs::set_permissions(&decrypted, permissions).unwrap();
+
+    let mut game = RpgGame::new(&game_dir, false).unwrap();
+    game.set_clear_readonly(true);
+    let results = game.decrypt_all(&OutputSettings::NextTo).unwrap();
+
+    assert!(results.iter().all(Result::is_ok));
+    assert_eq!(fs::read(&decrypted).unwrap().len(), IMG_ENC.len() - 16);
+}
+
+#[test]
+fn test_decrypt_all_max_in_flight_bytes() {
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let game_dir = create_test_game(tmp_dir.path());
+    for i in 0..8 {
+        fs::write(
+            game_dir.join(format!("www/img/pictures/test{}.rpgmvp", i)),
+            IMG_ENC,
+        )
+        .unwrap();
+    }
+
+    let mut game = RpgGame::new(&game_dir, false).unwrap();
+    game.set_max_in_flight_bytes(Some(IMG_ENC.len() as u64 / 2));
+    let results = game.decrypt_all(&OutputSettings::NextTo).unwrap();
+
+    assert_eq!(results.len(), 9);
+    assert!(results.iter().all(Result::is_ok));
+}
+
+#[test]
+fn test_sniff_extension() {
+    let file = |data: &[u8]| unsafe {
+        RpgFile::from_parts(
+            data.to_vec(),
+            RpgFileType::Audio,
+            PathBuf::from("test_files/test.rpgmvo"),
+        )
+    };
+
+    assert_eq!(file(b"OggS\0\x02").sniff_extension(), Some("ogg"));
+    assert_eq!(
+        file(b"\0\0\0\x20ftypM4A \0\0").sniff_extension(),
+        Some("m4a")
+    );
+    assert_eq!(
+        file(b"\0\0\0\x20ftypisom\0\0").sniff_extension(),
+        Some("mp4")
+    );
+    assert_eq!(
+        file(&[0x1A, 0x45, 0xDF, 0xA3]).sniff_extension(),
+        Some("webm")
+    );
+    assert_eq!(
+        file(b"RIFF\x24\0\0\0WEBPVP8 ").sniff_extension(),
+        Some("webp")
+    );
+    assert_eq!(file(b"RIFF\x24\0\0\0WAVEfmt ").sniff_extension(), None);
+    assert_eq!(file(b"unknown").sniff_extension(), None);
+}
+
+#[test]
+fn test_verify_decrypted_webp() {
+    let file = unsafe {
+        RpgFile::from_parts(
+            b"RIFF\x24\0\0\0WEBPVP8 ".to_vec(),
+            RpgFileType::Image,
+            PathBuf::from("test.png_"),
+        )
+    };
+
+    assert!(file.verify_decrypted());
+}
+
+#[test]
+fn test_export_decrypted() {
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let game_dir = create_test_game(tmp_dir.path());
+    fs::create_dir_all(game_dir.join("www/js")).unwrap();
+    fs::write(game_dir.join("www/js/main.js"), "// main").unwrap();
+    let dest = tmp_dir.path().join("export");
+
+    let game = RpgGame::new(&game_dir, false).unwrap();
+    let report = game.export_decrypted(&dest).unwrap();
+
+    assert_eq!(report.decrypted, 1);
+    assert_eq!(report.copied, 2);
+    assert!(report.failed.is_empty());
+
+    assert_eq!(fs::read(dest.join("www/js/main.js")).unwrap(), b"// main");
+    assert!(dest.join("www/img/pictures/test.png").exists());
+    assert!(!dest.join("www/img/pictures/test.rpgmvp").exists());
+    assert!(!RpgGame::new(&dest, false).unwrap().is_encrypted());
+    assert!(RpgGame::new(&game_dir, false).unwrap().is_encrypted());
+
+    let result = game.export_decrypted(&dest);
+    assert!(matches!(result, Err(Error::OutputDirExists(_))));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_export_decrypted_hard_link_unchanged() {
+    use std::os::unix::fs::MetadataExt;
+
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let game_dir = create_test_game(tmp_dir.path());
+    fs::create_dir_all(game_dir.join("www/js")).unwrap();
+    fs::write(game_dir.join("www/js/main.js"), "// main").unwrap();
+    let dest = tmp_dir.path().join("export");
+
+    let mut game = RpgGame::new(&game_dir, false).unwrap();
+    game.set_hard_link_unchanged(true);
+    let report = game.export_decrypted(&dest).unwrap();
+
+    assert_eq!(report.decrypted, 1);
+    assert_eq!(report.copied, 2);
+    assert!(report.failed.is_empty());
+
+    let ino = |path: &Path| fs::metadata(path).unwrap().ino();
+    assert_eq!(
+        ino(&game_dir.join("www/js/main.js")),
+        ino(&dest.join("www/js/main.js"))
+    );
+    assert_ne!(
+        ino(&game_dir.join("www/data/System.json")),
+        ino(&dest.join("www/data/System.json"))
+    );
+    assert!(!RpgGame::new(&dest, false).unwrap().is_encrypted());
+    assert!(RpgGame::new(&game_dir, false).unwrap().is_encrypted());
+}
+
+#[test]
+fn test_assets_fingerprint() {
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let game_dir = create_test_game(tmp_dir.path());
+
+    let game = RpgGame::new(&game_dir, false).unwrap();
+    let fingerprint = game.assets_fingerprint().unwrap();
+    assert_eq!(fingerprint, game.assets_fingerprint().unwrap());
+
+    fs::write(game_dir.join("www/img/pictures/test2.rpgmvp"), IMG_ENC).unwrap();
+    assert_ne!(fingerprint, game.assets_fingerprint().unwrap());
+}
+
+#[test]
+fn test_in_place_rename() {
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let game_dir = create_test_game(tmp_dir.path());
+    let pictures = game_dir.join("www/img/pictures");
+    fs::write(pictures.join("mz.png_"), IMG_ENC).unwrap();
+
+    let mut game = RpgGame::new(&game_dir, false).unwrap();
+    let results = game.decrypt_all(&OutputSettings::InPlaceRename).unwrap();
+    assert!(results.iter().all(Result::is_ok));
+
+    // MV
+    assert!(!pictures.join("test.rpgmvp").exists());
+    assert!(pictures.join("test.png").exists());
+
+    // MZ
+    assert!(!pictures.join("mz.png_").exists());
+    assert_eq!(
+        fs::read(pictures.join("mz.png")).unwrap(),
+        fs::read(pictures.join("test.png")).unwrap()
+    );
+
+    assert!(!RpgGame::new(&game_dir, false).unwrap().is_encrypted());
+}
+
+#[test]
+fn test_check_encrypted_lenient_flags() {
+    use crate::check_encrypted;
+
+    let flags = |audio: serde_json::Value, img: serde_json::Value| {
+        check_encrypted(&serde_json::json!({
+            "hasEncryptedAudio": audio,
+            "hasEncryptedImages": img,
+        }))
+    };
+
+    assert!(flags(false.into(), "TRUE".into()).unwrap());
+    assert!(flags(1.into(), false.into()).unwrap());
+    assert!(!flags("False".into(), 0.into()).unwrap());
+    assert!(flags("yes".into(), false.into()).is_err());
+    assert!(flags(2.into(), false.into()).is_err());
+}
+
+#[test]
+fn test_to_decrypted() {
+    let mut file = unsafe {
+        RpgFile::from_parts(
+            IMG_ENC.to_vec(),
+            RpgFileType::Image,
+            PathBuf::from("test_images/test.rpgmvp"),
+        )
+    };
+
+    let decrypted = file.to_decrypted(&key()).unwrap();
+    assert_eq!(file.data, IMG_ENC);
+
+    file.decrypt(&key()).unwrap();
+    assert_eq!(file.data, decrypted);
+}
+
+#[test]
+fn test_decrypt_observer() {
+    #[derive(Default)]
+    struct Recorder {
+        started: usize,
+        done: Vec<(PathBuf, u64)>,
+        errors: usize,
+    }
+
+    impl DecryptObserver for Recorder {
+        fn on_file_start(&mut self, _path: &Path) {
+            self.started += 1;
+        }
+
+        fn on_file_done(&mut self, _path: &Path, new_path: &Path, bytes: u64) {
+            self.done.push((new_path.to_path_buf(), bytes));
+        }
+
+        fn on_file_error(&mut self, _path: &Path, _err: &Error) {
+            self.errors += 1;
+        }
+    }
+
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let game_dir = create_test_game(tmp_dir.path());
+    fs::write(game_dir.join("www/img/pictures/short.rpgmvp"), "short").unwrap();
+
+    let mut recorder = Recorder::default();
+    let mut game = RpgGame::new(&game_dir, false).unwrap();
+    game.decrypt_all_with(&OutputSettings::NextTo, &mut recorder)
+        .unwrap();
+
+    let new_path = game_dir.join("www/img/pictures/test.png");
+    assert_eq!(recorder.started, 2);
+    assert_eq!(recorder.errors, 1);
+    assert_eq!(
+        recorder.done,
+        vec![(new_path.clone(), fs::metadata(&new_path).unwrap().len())]
+    );
+}
+
+#[test]
+fn test_encrypt_decrypt_round_trip_min_len() {
+    let file = |data: Vec<u8>| unsafe {
+        RpgFile::from_parts(data, RpgFileType::Image, PathBuf::from("a.png"))
+    };
+
+    let mut too_short = file(vec![1; 16]);
+    assert!(matches!(
+        too_short.encrypt(&key(), false),
+        Err(Error::FileTooShort(_))
+    ));
+    assert_eq!(too_short.data, vec![1; 16]);
+
+    let mut shortest = file(vec![1; 17]);
+    shortest.encrypt(&key(), false).unwrap();
+    shortest.decrypt(&key()).unwrap();
+    assert_eq!(shortest.data, vec![1; 17]);
+}
+
+#[test]
+fn test_encrypt_decrypt_round_trip() {
+    let mut file = unsafe {
+        RpgFile::from_parts(
+            IMG_ENC.to_vec(),
+            RpgFileType::Image,
+            PathBuf::from("test_images/test.rpgmvp"),
+        )
+    };
+
+    file.decrypt(&key()).unwrap();
+    file.encrypt(&key(), true).unwrap();
+    assert_eq!(file.data, IMG_ENC);
+
+    // decrypt() discards the RpgMaker header, so encrypt() always writes
+    // the canonical one. A non-standard header is not preserved.
+    let mut modified = IMG_ENC.to_vec();
+    modified[10] = 0xFF;
+    let mut file = unsafe {
+        RpgFile::from_parts(
+            modified,
+            RpgFileType::Image,
+            PathBuf::from("test_images/test.rpgmvp"),
+        )
+    };
+
+    file.decrypt(&key()).unwrap();
+    file.encrypt(&key(), true).unwrap();
+    assert_eq!(file.data, IMG_ENC);
+}
+
+#[test]
+fn test_files_filtered() {
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let game_dir = create_test_game(tmp_dir.path());
+    fs::create_dir_all(game_dir.join("www/audio")).unwrap();
+    fs::write(game_dir.join("www/audio/test.rpgmvo"), "audio").unwrap();
+
+    let game = RpgGame::new(&game_dir, false).unwrap();
+    let files = game
+        .files_filtered(|path| path.starts_with(game_dir.join("www/img")))
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].file_type, RpgFileType::Image);
+    assert_eq!(files[0].data, IMG_ENC);
+}
+
+#[test]
+fn test_output_settings_from_str() {
+    assert_eq!(
+        "in-place".parse::<OutputSettings>().unwrap(),
+        OutputSettings::NextTo
+    );
+    assert_eq!(
+        "overwrite".parse::<OutputSettings>().unwrap(),
+        OutputSettings::Replace
+    );
+    assert_eq!(
+        "specific:C:\\out".parse::<OutputSettings>().unwrap(),
+        OutputSettings::Output {
+            dir: PathBuf::from("C:\\out")
+        }
+    );
+    assert_eq!(
+        "suffixed:_dec".parse::<OutputSettings>().unwrap(),
+        OutputSettings::InPlaceSuffixed {
+            suffix: "_dec".to_string()
+        }
+    );
+
+    assert_eq!(
+        "flatten-depth:2:out".parse::<OutputSettings>().unwrap(),
+        OutputSettings::FlattenDepth {
+            dir: PathBuf::from("out"),
+            keep_depth: 2
+        }
+    );
+
+    for invalid in [
+        "",
+        "flatten",
+        "flatten:",
+        "overwrite:out",
+        "replace",
+        "flatten-depth:out",
+        "flatten-depth:x:out",
+    ] {
+        assert!(matches!(
+            invalid.parse::<OutputSettings>(),
+            Err(Error::InvalidOutputSettings(_))
+        ));
+    }
+}
+
+#[test]
+fn test_supported_extensions() {
+    use crate::rpg_file::{supported_decrypted_extensions, supported_encrypted_extensions};
+
+    for ext in supported_encrypted_extensions() {
+        let path = PathBuf::from(format!("test.{}", ext));
+        let file_type = RpgFileType::scan(&path).unwrap();
+        assert!(supported_decrypted_extensions().contains(&file_type.to_extension().as_str()));
+    }
+
+    assert_eq!(supported_encrypted_extensions().len(), 6);
+    assert!(RpgFileType::scan(Path::new("test.png")).is_none());
+}
+
+#[cfg(feature = "ignore")]
+#[test]
+fn test_rrdignore() {
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let game_dir = create_test_game(tmp_dir.path());
+    fs::create_dir_all(game_dir.join("www/img/vendor")).unwrap();
+    fs::write(game_dir.join("www/img/vendor/logo.rpgmvp"), IMG_ENC).unwrap();
+    fs::write(game_dir.join(".rrdignore"), "www/img/vendor/\n").unwrap();
+
+    let mut game = RpgGame::new(&game_dir, false).unwrap();
+    assert_eq!(game.scan_files().unwrap().len(), 1);
+
+    let dest = tmp_dir.path().join("export");
+    let report = game.export_decrypted(&dest).unwrap();
+    assert_eq!(report.decrypted, 1);
+    assert_eq!(report.ignored, 1);
+    assert_eq!(
+        fs::read(dest.join("www/img/vendor/logo.rpgmvp")).unwrap(),
+        IMG_ENC
+    );
+
+    game.decrypt_all(&OutputSettings::NextTo).unwrap();
+    assert!(!game_dir.join("www/img/vendor/logo.png").exists());
+}
+
+#[test]
+fn test_encrypted_path_of() {
+    use crate::rpg_file::{decrypted_path_of, encrypted_path_of, RpgEngine};
+
+    let ogg = Path::new("audio/se/click.ogg");
+    let mv = encrypted_path_of(ogg, RpgEngine::Mv).unwrap();
+    let mz = encrypted_path_of(ogg, RpgEngine::Mz).unwrap();
+
+    assert_eq!(mv, PathBuf::from("audio/se/click.rpgmvo"));
+    assert_eq!(mz, PathBuf::from("audio/se/click.ogg_"));
+    assert_eq!(decrypted_path_of(&mv).as_deref(), Some(ogg));
+    assert_eq!(decrypted_path_of(&mz).as_deref(), Some(ogg));
+
+    assert_eq!(decrypted_path_of(ogg), None);
+    assert_eq!(
+        encrypted_path_of(Path::new("readme.txt"), RpgEngine::Mv),
+        None
+    );
+}
+
+#[test]
+fn test_ambiguous_system_json() {
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let game_dir = create_test_game(tmp_dir.path());
+    let other = game_dir.join("data/System.json");
+    fs::create_dir_all(other.parent().unwrap()).unwrap();
+
+    // same key, not ambiguous
+    fs::copy(game_dir.join("www/data/System.json"), &other).unwrap();
+    assert!(RpgGame::new(&game_dir, false).is_ok());
+
+    fs::write(
+        &other,
+        r#"{"hasEncryptedImages":true,"encryptionKey":"f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0"}"#,
+    )
+    .unwrap();
+    let result = RpgGame::new(&game_dir, false);
+    assert!(
+        matches!(result, Err(Error::AmbiguousSystemJson { candidates }) if candidates.len() == 2)
+    );
+
+    let game = RpgGame::with_system_json(&game_dir, &other, false).unwrap();
+    assert_eq!(game.get_key().bytes, &[0xf0; 16]);
+}
+
+#[test]
+fn test_decrypt_all_skips_unencrypted_game() {
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let game_dir = create_test_game(tmp_dir.path());
+    fs::write(
+        game_dir.join("www/data/System.json"),
+        r#"{"hasEncryptedAudio":false,"hasEncryptedImages":false,"encryptionKey":"0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f"}"#,
+    )
+    .unwrap();
+
+    let mut game = RpgGame::new(&game_dir, false).unwrap();
+    assert!(matches!(
+        game.decrypt_all(&OutputSettings::NextTo),
+        Err(Error::AlreadyDecrypted)
+    ));
+    assert!(!game_dir.join("www/img/pictures/test.png").exists());
+
+    game.set_force(true);
+    assert_eq!(game.decrypt_all(&OutputSettings::NextTo).unwrap().len(), 1);
+    assert!(game_dir.join("www/img/pictures/test.png").exists());
+}
+
+#[test]
+fn test_error_kind() {
+    use crate::error::ErrorKind;
+    use std::io;
+
+    let too_short = Error::FileTooShort(PathBuf::from("test.rpgmvp"));
+    assert_eq!(too_short.kind(), ErrorKind::File);
+    assert!(too_short.is_recoverable());
+
+    assert_eq!(Error::SystemJsonNotFound.kind(), ErrorKind::SystemJson);
+    assert!(!Error::SystemJsonNotFound.is_recoverable());
+    assert!(!Error::SystemJsonInvalidKey {
+        key: "encryptionKey".to_string()
+    }
+    .is_recoverable());
+
+    let interrupted = Error::from(io::Error::from(io::ErrorKind::Interrupted));
+    assert_eq!(interrupted.kind(), ErrorKind::Io);
+    assert!(interrupted.is_recoverable());
+    assert!(!Error::from(io::Error::from(io::ErrorKind::NotFound)).is_recoverable());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_decrypt_all_file_mode() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let game_dir = create_test_game(tmp_dir.path());
+
+    let mut game = RpgGame::new(&game_dir, false).unwrap();
+    game.set_file_mode(Some(0o640));
+    game.decrypt_all(&OutputSettings::NextTo).unwrap();
+
+    let metadata = fs::metadata(game_dir.join("www/img/pictures/test.png")).unwrap();
+    assert_eq!(metadata.permissions().mode() & 0o777, 0o640);
+}
+
+#[test]
+fn test_decrypt_all_keeps_name_prefixes() {
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let out_dir = tmp_dir.path().join("out");
+    let in_game = |path: &str| PathBuf::from("game/www/img/pictures").join(path);
+
+    let modes = [
+        (OutputSettings::NextTo, in_game("{}.png")),
+        (OutputSettings::Replace, in_game("{}.png")),
+        (OutputSettings::InPlaceRename, in_game("{}.png")),
+        (
+            OutputSettings::InPlaceSuffixed {
+                suffix: "_dec".to_string(),
+            },
+            in_game("{}_dec.png"),
+        ),
+        (
+            OutputSettings::Output {
+                dir: out_dir.clone(),
+            },
+            PathBuf::from("out/www/img/pictures/{}.png"),
+        ),
+        (
+            OutputSettings::Flatten {
+                dir: out_dir.clone(),
+            },
+            PathBuf::from("out/www_img_pictures_{}.png"),
+        ),
+    ];
+
+    for (mode, expected) in modes {
+        let _ = fs::remove_dir_all(tmp_dir.path().join("game"));
+        let _ = fs::remove_dir_all(&out_dir);
+        let game_dir = create_test_game(tmp_dir.path());
+        let pictures = game_dir.join("www/img/pictures");
+        fs::write(pictures.join("$Hero.rpgmvp"), IMG_ENC).unwrap();
+        fs::write(pictures.join("!Door.png_"), IMG_ENC).unwrap();
+
+        let mut game = RpgGame::new(&game_dir, false).unwrap();
+        let results = game.decrypt_all(&mode).unwrap();
+        assert!(results.iter().all(Result::is_ok), "{:?}", mode);
+
+        for name in ["$Hero", "!Door"] {
+            let path = tmp_dir
+                .path()
+                .join(expected.to_string_lossy().replace("{}", name));
+            assert!(path.exists(), "{:?}: {} missing", mode, path.display());
+        }
+    }
+}
+
+#[test]
+fn test_summary() {
+    use crate::rpg_file::RpgEngine;
+
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let game_dir = create_test_game(tmp_dir.path());
+
+    let summary = RpgGame::new(&game_dir, false).unwrap().summary();
+    assert_eq!(summary.title, None);
+    assert_eq!(summary.version, None);
+    assert_eq!(summary.engine, RpgEngine::Mv);
+    assert!(summary.has_encrypted_images);
+    assert!(summary.has_encrypted_audio);
+    assert_eq!(summary.key_hex, "0f".repeat(16));
+    assert_eq!(
+        summary.system_json_path,
+        game_dir.join("www/data/System.json")
+    );
+}
+
+#[test]
+fn test_scan_index() {
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let game_dir = create_test_game(tmp_dir.path());
+
+    let mut game = RpgGame::new(&game_dir, false).unwrap();
+    let index = game.scan_index().unwrap();
+    assert_eq!(index.count(&RpgFileType::Image), 1);
+    assert_eq!(index.count(&RpgFileType::Audio), 0);
+    assert_eq!(index.total_size(), IMG_ENC.len() as u64);
+
+    // cached until rescanned
+    fs::write(game_dir.join("www/img/pictures/test2.rpgmvp"), IMG_ENC).unwrap();
+    assert_eq!(game.scan_index().unwrap().entries.len(), 1);
+
+    let index = game.rescan().unwrap();
+    assert_eq!(index.entries.len(), 2);
+    assert_eq!(index.size_of(&RpgFileType::Image), 2 * IMG_ENC.len() as u64);
+}
+
+#[test]
+fn test_scan_uppercase_extension() {
+    assert_eq!(
+        RpgFileType::scan(Path::new("img/Title.RPGMVP")),
+        Some(RpgFileType::Image)
+    );
+    assert_eq!(
+        RpgFileType::scan(Path::new("audio/Theme.Ogg_")),
+        Some(RpgFileType::Audio)
+    );
+}
+
+#[test]
+fn test_trailer_hint() {
+    let mut file = unsafe {
+        RpgFile::from_parts(
+            IMG_ENC.to_vec(),
+            RpgFileType::Image,
+            PathBuf::from("test_images/test.rpgmvp"),
+        )
+    };
+    assert_eq!(file.trailer_hint(), None);
+
+    file.data.extend_from_slice(b"trailer");
+    assert_eq!(file.trailer_hint(), Some(&b"trailer"[..]));
+
+    file.decrypt(&key()).unwrap();
+    assert_eq!(file.trailer_hint(), Some(&b"trailer"[..]));
+
+    // a single ogg page with one 4 byte segment
+    let mut ogg = b"OggS".to_vec();
+    ogg.resize(26, 0);
+    ogg.extend_from_slice(&[1, 4, 1, 2, 3, 4]);
+    let mut file = unsafe { RpgFile::from_parts(ogg, RpgFileType::Audio, PathBuf::new()) };
+    assert_eq!(file.trailer_hint(), None);
+
+    file.data.extend_from_slice(b"trailer");
+    assert_eq!(file.trailer_hint(), Some(&b"trailer"[..]));
+}
+
+#[test]
+fn test_decrypt_all_prints_nothing() {
+    // the test harness captures stdout of tests, so the actual
+    // decryption runs in a child process running only this test
+    const GAME_DIR_VAR: &str = "RRD_TEST_PRINTS_NOTHING_GAME_DIR";
+
+    if let Some(game_dir) = std::env::var_os(GAME_DIR_VAR) {
+        let mut game = RpgGame::new(PathBuf::from(game_dir), false).unwrap();
+        print!("<start>");
+        let results = game.decrypt_all(&OutputSettings::NextTo);
+        print!("<end>");
+        assert!(results.unwrap().iter().all(Result::is_ok));
+        return;
+    }
+
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let game_dir = create_test_game(tmp_dir.path());
+
+    let output = std::process::Command::new(std::env::current_exe().unwrap())
+        .args([
+            "--exact",
+            "tests::test_decrypt_all_prints_nothing",
+            "--nocapture",
+        ])
+        .env(GAME_DIR_VAR, &game_dir)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("<start><end>"));
+    assert!(game_dir.join("www/img/pictures/test.png").exists());
+}
+
+#[test]
+fn test_open_encrypted_and_decrypted() {
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let game_dir = create_test_game(tmp_dir.path());
+    let game = RpgGame::new(&game_dir, false).unwrap();
+
+    let mut file = game.open_encrypted("www/img/pictures/test.rpgmvp").unwrap();
+    assert!(matches!(
+        game.open_decrypted("www/img/pictures/test.rpgmvp"),
+        Err(Error::NotRpgFile(_))
+    ));
+
+    file.decrypt(&key()).unwrap();
+    fs::write(&file.new_path, &file.data).unwrap();
+    fs::write(game_dir.join("www/img/pictures/fake.rpgmvp"), &file.data).unwrap();
+    fs::write(game_dir.join("www/img/pictures/fake.png"), IMG_ENC).unwrap();
+
+    let decrypted = game.open_decrypted("www/img/pictures/test.png").unwrap();
+    assert_eq!(decrypted.file_type, RpgFileType::Image);
+    assert_eq!(decrypted.data, file.data);
+
+    assert!(matches!(
+        game.open_encrypted("www/img/pictures/fake.rpgmvp"),
+        Err(Error::FileNotEncrypted(_))
+    ));
+    assert!(matches!(
+        game.open_decrypted("www/img/pictures/fake.png"),
+        Err(Error::AlreadyEncrypted(_))
+    ));
+}
+
+#[test]
+fn test_new_empty_key() {
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let game_dir = create_test_game(tmp_dir.path());
+    fs::write(
+        game_dir.join("www/data/System.json"),
+        r#"{"hasEncryptedAudio":true,"hasEncryptedImages":true,"encryptionKey":""}"#,
+    )
+    .unwrap();
+
+    assert!(matches!(
+        RpgGame::new(&game_dir, false),
+        Err(Error::EmptyKey)
+    ));
+}
+
+#[test]
+fn test_walk_parallel() {
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let game_dir = create_test_game(tmp_dir.path());
+    for dir in ["www/img/a", "www/img/a.b", "www/audio/bgm"] {
+        fs::create_dir_all(game_dir.join(dir)).unwrap();
+    }
+    for file in [
+        "www/img/a/z.rpgmvp",
+        "www/img/a.b/a.rpgmvp",
+        "www/img/a.rpgmvp",
+        "www/audio/bgm/Theme.rpgmvo",
+        "www/audio/bgm/theme.ogg_",
+    ] {
+        fs::write(game_dir.join(file), IMG_ENC).unwrap();
+    }
+
+    let game = RpgGame::new(&game_dir, false).unwrap();
+    let serial = game.walk().unwrap();
+    assert_eq!(serial.len(), 6);
+    assert_eq!(game.walk_parallel().unwrap(), serial);
+}
+
+#[test]
+fn test_decrypt_all_skip_if_decrypted_exists() {
+    #[derive(Default)]
+    struct Skipped(Vec<PathBuf>);
+
+    impl DecryptObserver for Skipped {
+        fn on_file_skipped(&mut self, path: &Path) {
+            self.0.push(path.to_path_buf());
+        }
+    }
+
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let game_dir = create_test_game(tmp_dir.path());
+    let pictures = game_dir.join("www/img/pictures");
+    fs::write(pictures.join("done.rpgmvp"), IMG_ENC).unwrap();
+    fs::write(pictures.join("done.png"), "already decrypted").unwrap();
+
+    for sniff_extensions in [false, true] {
+        let _ = fs::remove_file(pictures.join("test.png"));
+
+        let mut game = RpgGame::new(&game_dir, false).unwrap();
+        game.set_skip_if_decrypted_exists(true);
+        game.set_sniff_extensions(sniff_extensions);
+
+        let mut skipped = Skipped::default();
+        let results = game
+            .decrypt_all_with(&OutputSettings::NextTo, &mut skipped)
+            .unwrap();
+
+        assert_eq!(results.len(), 1);
+        assert_eq!(skipped.0, vec![pictures.join("done.rpgmvp")]);
+        assert!(pictures.join("test.png").exists());
+        assert_eq!(
+            fs::read(pictures.join("done.png")).unwrap(),
+            b"already decrypted"
+        );
+    }
+}
+
+#[test]
+fn test_key_parse_base64() {
+    use crate::key::KeyEncoding;
+
+    assert_eq!(
+        Key::parse("0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f").unwrap(),
+        (key(), KeyEncoding::Hex)
+    );
+    assert_eq!(
+        Key::parse("Dw8PDw8PDw8PDw8PDw8PDw==").unwrap(),
+        (key(), KeyEncoding::Base64)
+    );
+    assert_eq!(
+        Key::parse("Dw8PDw8PDw8PDw8PDw8PDw").unwrap(),
+        (key(), KeyEncoding::Base64)
+    );
+    assert!(Key::parse("not a key!").is_err());
+
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let game_dir = create_test_game(tmp_dir.path());
+    fs::write(
+        game_dir.join("www/data/System.json"),
+        r#"{"hasEncryptedImages":true,"encryptionKey":"Dw8PDw8PDw8PDw8PDw8PDw=="}"#,
+    )
+    .unwrap();
+
+    let game = RpgGame::new(&game_dir, false).unwrap();
+    assert_eq!(game.key_encoding(), KeyEncoding::Base64);
+    assert_eq!(game.get_key().bytes, key().as_bytes());
+}
+
+#[test]
+fn test_decrypt_all_staged_output() {
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let game_dir = create_test_game(tmp_dir.path());
+    let out_dir = tmp_dir.path().join("out");
+    let output = OutputSettings::Output {
+        dir: out_dir.clone(),
+    };
+
+    // a broken file fails the run, nothing may end up in the output dir
+    let short = game_dir.join("www/img/pictures/short.rpgmvp");
+    fs::write(&short, "short").unwrap();
+
+    let mut game = RpgGame::new(&game_dir, false).unwrap();
+    game.set_staged_output(true);
+    let results = game.decrypt_all(&output).unwrap();
+    assert!(results.iter().any(Result::is_err));
+    assert!(!out_dir.exists());
+    assert!(!tmp_dir.path().join(".out.rrd-staging").exists());
+
+    fs::remove_file(short).unwrap();
+    let results = game.decrypt_all(&output).unwrap();
+    assert!(results.iter().all(Result::is_ok));
+    assert!(out_dir.join("www/img/pictures/test.png").exists());
+    assert!(!tmp_dir.path().join(".out.rrd-staging").exists());
+
+    assert!(matches!(
+        game.decrypt_all(&output),
+        Err(Error::OutputDirExists(_))
+    ));
+}
+
+#[test]
+fn test_encrypted_and_decrypted_len() {
+    let mut file = unsafe {
+        RpgFile::from_parts(
+            IMG_ENC.to_vec(),
+            RpgFileType::Image,
+            PathBuf::from("test_images/test.rpgmvp"),
+        )
+    };
+    assert_eq!(file.encrypted_len(), IMG_ENC.len());
+    assert_eq!(file.decrypted_len(), IMG_ENC.len() - 16);
+
+    file.decrypt(&key()).unwrap();
+    assert_eq!(file.encrypted_len(), IMG_ENC.len());
+    assert_eq!(file.decrypted_len(), file.data.len());
+
+    file.data = IMG_ENC[..16].to_vec();
+    assert_eq!(file.decrypted_len(), 0);
+}
+
+#[test]
+fn test_path_accessors() {
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let game_path = create_test_game(tmp_dir.path());
+
+    let game = RpgGame::new(&game_path, false).unwrap();
+
+    assert_eq!(game.path(), game_path);
+    assert_eq!(
+        game.system_json_path(),
+        game_path.join("www/data/System.json")
+    );
+}
+
+#[test]
+fn test_decrypt_header() {
+    let file = unsafe {
+        RpgFile::from_parts(
+            IMG_ENC.to_vec(),
+            RpgFileType::Image,
+            PathBuf::from("test.rpgmvp"),
+        )
+    };
+
+    let header = file.decrypt_header(&key()).unwrap();
+
+    assert_eq!(header[..], file.to_decrypted(&key()).unwrap()[..16]);
+    assert!(header.starts_with(b"\x89PNG"));
+    assert_eq!(file.data, IMG_ENC);
+}
+
+#[test]
+fn test_decrypt_header_too_short() {
+    let file = unsafe {
+        RpgFile::from_parts(
+            IMG_ENC[..20].to_vec(),
+            RpgFileType::Image,
+            PathBuf::from("test.rpgmvp"),
+        )
+    };
+
+    assert!(matches!(
+        file.decrypt_header(&key()),
+        Err(Error::FileTooShort(_))
+    ));
+}
+
+#[test]
+fn test_decrypt_all_report() {
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let game_path = create_test_game(tmp_dir.path());
+
+    let mut game = RpgGame::new(&game_path, false).unwrap();
+    let report = game.decrypt_all_report(&OutputSettings::NextTo).unwrap();
+
+    assert_eq!(report.decrypted, 1);
+    assert!(report.failed.is_empty());
+    assert_eq!(report.bytes, IMG_ENC.len() as u64 - 16);
+    assert!(report.bytes_per_second >= 0.0);
+}
+
+#[test]
+fn test_new_uppercase_www_dir() {
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let game_path = create_test_game(tmp_dir.path());
+    fs::rename(game_path.join("www"), game_path.join("WWW")).unwrap();
+
+    let game = RpgGame::new(&game_path, false).unwrap();
+
+    assert_eq!(
+        game.system_json_path(),
+        game_path.join("WWW/data/System.json")
+    );
+    assert_eq!(game.summary().engine, RpgEngine::Mv);
+}
+
+#[test]
+fn test_plan() {
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let game_path = create_test_game(tmp_dir.path());
+    let game = RpgGame::new(&game_path, false).unwrap();
+
+    let plan = game
+        .plan(&OutputSettings::Output {
+            dir: tmp_dir.path().join("out"),
+        })
+        .unwrap();
+
+    assert_eq!(
+        plan,
+        vec![(
+            game_path.join("www/img/pictures/test.rpgmvp"),
+            tmp_dir.path().join("out/www/img/pictures/test.png")
+        )]
+    );
+    assert!(!tmp_dir.path().join("out").exists());
+}
+
+#[test]
+fn test_plan_flatten_collision() {
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let game_path = create_test_game(tmp_dir.path());
+    fs::write(game_path.join("www/img/pictures/test.png_"), IMG_ENC).unwrap();
+    let game = RpgGame::new(&game_path, false).unwrap();
+
+    let flatten = OutputSettings::Flatten {
+        dir: tmp_dir.path().join("out"),
+    };
+    assert!(matches!(
+        game.plan(&flatten),
+        Err(Error::OutputPathCollision(collisions)) if collisions.len() == 1
+    ));
+
+    let by_type = OutputSettings::ByType {
+        dir: tmp_dir.path().join("out"),
+    };
+    let dests: Vec<_> = game
+        .plan(&by_type)
+        .unwrap()
+        .into_iter()
+        .map(|(_, dest)| dest)
+        .collect();
+    assert_eq!(
+        dests,
+        vec![
+            tmp_dir.path().join("out/img/test.png"),
+            tmp_dir.path().join("out/img/test_1.png")
+        ]
+    );
+}
+
+#[test]
+fn test_rpg_file_from_reader() {
+    let mut file = RpgFile::from_reader(IMG_ENC, RpgFileType::Image).unwrap();
+
+    assert_eq!(file.orig_path, PathBuf::from("stream.rpgmvp"));
+    assert_eq!(file.new_path, PathBuf::from("stream.png"));
+
+    file.decrypt(&key()).unwrap();
+    assert!(file.verify_decrypted());
+}
+
+#[test]
+fn test_max_file_size() {
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let game_path = create_test_game(tmp_dir.path());
+
+    let mut game = RpgGame::new(&game_path, false).unwrap();
+    game.set_max_file_size(Some(IMG_ENC.len() as u64 - 1));
+    let report = game.decrypt_all_report(&OutputSettings::NextTo).unwrap();
+
+    assert_eq!(report.decrypted, 0);
+    assert_eq!(report.failed.len(), 1);
+    assert_eq!(report.failed[0].kind, ErrorKind::File);
+    assert_eq!(
+        report.failed[0].path,
+        Some(game_path.join("www/img/pictures/test.rpgmvp"))
+    );
+    assert!(!game_path.join("www/img/pictures/test.png").exists());
+}
+
+#[test]
+fn test_error_info() {
+    let error = Error::KeyMismatch(PathBuf::from("img/actor1.rpgmvp"));
+
+    let info = ErrorInfo::from(&error);
+
+    assert_eq!(info.kind, ErrorKind::Key);
+    assert_eq!(info.message, error.to_string());
+    assert_eq!(info.path, Some(PathBuf::from("img/actor1.rpgmvp")));
+    assert_eq!(info.clone(), info);
+}
+
+#[test]
+fn test_set_encrypted() {
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let game_path = create_test_game(tmp_dir.path());
+
+    let mut game = RpgGame::new(&game_path, false).unwrap();
+    game.set_encrypted(false).unwrap();
+    assert!(!game.is_encrypted());
+
+    let system_json = fs::read_to_string(game_path.join("www/data/System.json")).unwrap();
+    let system_json: serde_json::Value = serde_json::from_str(&system_json).unwrap();
+    assert_eq!(system_json["hasEncryptedAudio"], false);
+    assert_eq!(system_json["hasEncryptedImages"], false);
+
+    let mut game = RpgGame::new(&game_path, false).unwrap();
+    assert!(!game.is_encrypted());
+    game.set_encrypted(true).unwrap();
+    assert!(RpgGame::new(&game_path, false).unwrap().is_encrypted());
+}
+
+#[test]
+fn test_file_type_predicates() {
+    assert!(RpgFileType::Image.is_image());
+    assert!(!RpgFileType::Image.is_audio());
+    assert!(RpgFileType::Audio.is_audio());
+    assert!(!RpgFileType::Audio.is_video());
+    assert!(RpgFileType::Video.is_video());
+    assert!(!RpgFileType::Video.is_image());
+}
+
+#[test]
+fn test_to_data_uri() {
+    let file = |data: &[u8]| unsafe {
+        RpgFile::from_parts(data.to_vec(), RpgFileType::Image, PathBuf::from("a.png_"))
+    };
+
+    assert_eq!(file(b"").to_data_uri().unwrap(), "data:image/png;base64,");
+    assert_eq!(
+        file(b"f").to_data_uri().unwrap(),
+        "data:image/png;base64,Zg=="
+    );
+    assert_eq!(
+        file(b"fo").to_data_uri().unwrap(),
+        "data:image/png;base64,Zm8="
+    );
+    assert_eq!(
+        file(b"foo").to_data_uri().unwrap(),
+        "data:image/png;base64,Zm9v"
+    );
+    assert_eq!(
+        file(&[0xff, 0xfe, 0x00, 0x3e]).to_data_uri().unwrap(),
+        "data:image/png;base64,//4APg=="
+    );
+    assert!(file(b"RIFF\0\0\0\0WEBPVP8 ")
+        .to_data_uri()
+        .unwrap()
+        .starts_with("data:image/webp;base64,"));
+
+    let audio = unsafe {
+        RpgFile::from_parts(
+            b"\0\0\0\x20ftypM4A ".to_vec(),
+            RpgFileType::Audio,
+            PathBuf::from("a.m4a_"),
+        )
+    };
+    assert!(audio
+        .to_data_uri()
+        .unwrap()
+        .starts_with("data:audio/mp4;base64,"));
+    assert!(matches!(
+        file(IMG_ENC).to_data_uri(),
+        Err(Error::AlreadyEncrypted(_))
+    ));
+}
+
+#[test]
+fn test_decrypt_all_skips_nested_output_dir() {
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let game_path = create_test_game(tmp_dir.path());
+    let output = OutputSettings::Output {
+        dir: game_path.join("decrypted"),
+    };
+
+    let mut game = RpgGame::new(&game_path, false).unwrap();
+    game.set_force(true);
+    assert_eq!(game.decrypt_all(&output).unwrap().len(), 1);
+
+    // leftovers of an earlier run that are still encrypted
+    fs::write(game_path.join("decrypted/leftover.rpgmvp"), IMG_ENC).unwrap();
+
+    let results = game.decrypt_all(&output).unwrap();
+    assert_eq!(results.len(), 1);
+    assert!(!game_path.join("decrypted/leftover.png").exists());
+    assert!(!game_path.join("decrypted/decrypted").exists());
+    assert_eq!(game.plan(&output).unwrap().len(), 1);
+}
+
+#[test]
+fn test_write_both() {
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let file = unsafe {
+        RpgFile::from_parts(
+            IMG_ENC.to_vec(),
+            RpgFileType::Image,
+            PathBuf::from("test.rpgmvp"),
+        )
+    };
+    let encrypted_dest = tmp_dir.path().join("encrypted/img/test.rpgmvp");
+    let decrypted_dest = tmp_dir.path().join("decrypted/img/test.png");
+
+    file.write_both(&key(), &encrypted_dest, &decrypted_dest)
+        .unwrap();
+
+    assert_eq!(fs::read(&encrypted_dest).unwrap(), IMG_ENC);
+    let hash = Sha256::digest(fs::read(&decrypted_dest).unwrap());
+    assert_eq!(format!("{:x}", hash), IMG_UNENC_HASH);
+    assert_eq!(file.data, IMG_ENC);
+}
+
+#[test]
+fn test_decrypt_all_writes_system_json_once() {
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let game_path = create_test_game(tmp_dir.path());
+    for i in 0..32 {
+        fs::write(
+            game_path.join(format!("www/img/pictures/test{}.rpgmvp", i)),
+            IMG_ENC,
+        )
+        .unwrap();
+    }
+    let system_json_path = game_path.join("www/data/System.json");
+
+    let mut game = RpgGame::new(&game_path, false).unwrap();
+    let results = game.decrypt_all(&OutputSettings::Replace).unwrap();
+    assert_eq!(results.len(), 33);
+    assert!(results.iter().all(Result::is_ok));
+
+    // the last write to disk was the one the game knows about
+    let on_disk = fs::read(&system_json_path).unwrap();
+    assert_eq!(game.system_json.checksum(), system_json::checksum(&on_disk));
+
+    let system_json: serde_json::Value = serde_json::from_slice(&on_disk).unwrap();
+    assert_eq!(system_json["hasEncryptedAudio"], false);
+    assert_eq!(system_json["hasEncryptedImages"], false);
+}
+
+#[test]
+fn test_restore_image_header() {
+    let mut file = unsafe {
+        RpgFile::from_parts(
+            IMG_ENC.to_vec(),
+            RpgFileType::Image,
+            PathBuf::from("test.rpgmvp"),
+        )
+    };
+
+    file.restore_image_header().unwrap();
+
+    let hash = Sha256::digest(&file.data);
+    assert_eq!(format!("{:x}", hash), IMG_UNENC_HASH);
+    assert!(matches!(
+        file.restore_image_header(),
+        Err(Error::FileNotEncrypted(_))
+    ));
+}
+
+#[test]
+fn test_restore_all_images() {
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let game_path = create_test_game(tmp_dir.path());
+    fs::create_dir_all(game_path.join("www/audio/bgm")).unwrap();
+    fs::write(game_path.join("www/audio/bgm/theme.rpgmvo"), IMG_ENC).unwrap();
+
+    // the key is never used
+    let game = RpgGame::from_parts(
+        &game_path,
+        SystemJson::from_path(game_path.join("www/data/System.json")).unwrap(),
+        Key::from_bytes(vec![0]).unwrap(),
+        false,
+    );
+    let report = game
+        .restore_all_images(&OutputSettings::Output {
+            dir: tmp_dir.path().join("out"),
+        })
+        .unwrap();
+
+    assert_eq!(report.decrypted, 1);
+    assert_eq!(report.skipped, 1);
+    assert!(report.failed.is_empty());
+
+    let restored = fs::read(tmp_dir.path().join("out/www/img/pictures/test.png")).unwrap();
+    assert_eq!(format!("{:x}", Sha256::digest(restored)), IMG_UNENC_HASH);
+    assert!(!tmp_dir.path().join("out/www/audio").exists());
+}
+
+#[cfg(feature = "thumbnails")]
+#[test]
+fn test_thumbnails() {
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let game_path = create_test_game(tmp_dir.path());
+    fs::create_dir_all(game_path.join("www/audio/bgm")).unwrap();
+    fs::write(game_path.join("www/audio/bgm/theme.rpgmvo"), IMG_ENC).unwrap();
+
+    let game = RpgGame::new(&game_path, false).unwrap();
+    let thumbnails = game.thumbnails(4).collect::<Result<Vec<_>, _>>().unwrap();
+
+    assert_eq!(thumbnails.len(), 1);
+    assert_eq!(
+        thumbnails[0].0,
+        game_path.join("www/img/pictures/test.rpgmvp")
+    );
+    let thumbnail = image::load_from_memory(&thumbnails[0].1).unwrap();
+    assert!(thumbnail.width() <= 4 && thumbnail.height() <= 4);
+}
+
+#[test]
+fn test_version() {
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let game_dir = create_test_game(tmp_dir.path());
+
+    fs::write(
+        game_dir.join("package.json"),
+        r#"{"name":"game","version":"1.2.0"}"#,
+    )
+    .unwrap();
+    let game = RpgGame::new(&game_dir, false).unwrap();
+    assert_eq!(game.version().as_deref(), Some("1.2.0"));
+
+    fs::write(
+        game_dir.join("www/data/System.json"),
+        r#"{"hasEncryptedImages":true,"encryptionKey":"0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f","versionId":38291744}"#,
+    )
+    .unwrap();
+    let game = RpgGame::new(&game_dir, false).unwrap();
+    assert_eq!(game.version().as_deref(), Some("38291744"));
+    assert_eq!(game.summary().version.as_deref(), Some("38291744"));
+}
+
+#[test]
+fn test_encrypted_files_par() {
+    use rayon::prelude::ParallelIterator;
+
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let game_dir = create_test_game(tmp_dir.path());
+    for i in 0..8 {
+        fs::write(
+            game_dir.join(format!("www/img/pictures/test{}.rpgmvp", i)),
+            IMG_ENC,
+        )
+        .unwrap();
+    }
+
+    let game = RpgGame::new(&game_dir, false).unwrap();
+    let files = game
+        .encrypted_files_par()
+        .map(|file| {
+            let mut file = file?;
+            file.decrypt(&key())?;
+            Ok(file)
+        })
+        .collect::<Result<Vec<_>, Error>>()
+        .unwrap();
+    assert_eq!(files.len(), 9);
+    assert!(files.iter().all(RpgFile::verify_decrypted));
+
+    fs::write(
+        game_dir.join("www/img/pictures/short.rpgmvp"),
+        &IMG_ENC[..20],
+    )
+    .unwrap();
+    let result = game
+        .encrypted_files_par()
+        .map(|file| {
+            let mut file = file?;
+            file.decrypt(&key())?;
+            Ok(file)
+        })
+        .collect::<Result<Vec<_>, Error>>();
+    assert!(matches!(result, Err(Error::FileTooShort(_))));
+}
+
+#[test]
+fn test_key_looks_valid() {
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let game_dir = create_test_game(tmp_dir.path());
+    let system_json_path = game_dir.join("www/data/System.json");
+
+    let game = RpgGame::new(&game_dir, false).unwrap();
+    assert!(game.key_looks_valid());
+
+    let game = RpgGame::from_parts(
+        &game_dir,
+        SystemJson::from_path(system_json_path).unwrap(),
+        Key::from_hex("0f").unwrap(),
+        false,
+    );
+    assert!(!game.key_looks_valid());
+}
+
+#[test]
+fn test_transform() {
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let game_dir = create_test_game(tmp_dir.path());
+
+    let mut game = RpgGame::new(&game_dir, false).unwrap();
+    game.set_transform(Some(Box::new(|file_type, mut data| {
+        assert_eq!(*file_type, RpgFileType::Image);
+        data.truncate(8);
+        Ok(data)
+    })));
+    let results = game.decrypt_all(&OutputSettings::NextTo).unwrap();
+    assert!(results.iter().all(Result::is_ok));
+
+    let written = fs::read(game_dir.join("www/img/pictures/test.png")).unwrap();
+    assert_eq!(written, b"\x89PNG\r\n\x1a\n");
+}
+
+#[test]
+fn test_transform_error() {
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let game_dir = create_test_game(tmp_dir.path());
+
+    let mut game = RpgGame::new(&game_dir, false).unwrap();
+    game.set_transform(Some(Box::new(|_, _| {
+        Err(std::io::Error::other("transcoding failed").into())
+    })));
+    let results = game.decrypt_all(&OutputSettings::NextTo).unwrap();
+
+    assert!(matches!(results.as_slice(), [Err(Error::IoError(_))]));
+    assert!(!game_dir.join("www/img/pictures/test.png").exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_game_eq_symlink() {
+    use std::collections::HashSet;
+
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let game_dir = create_test_game(tmp_dir.path());
+    let link = tmp_dir.path().join("link");
+    std::os::unix::fs::symlink(&game_dir, &link).unwrap();
+
+    let game = RpgGame::new(&game_dir, false).unwrap();
+    let linked = RpgGame::new(&link, false).unwrap();
+    assert_eq!(game, linked);
+
+    let other_dir = create_test_game(&tmp_dir.path().join("other"));
+    let other = RpgGame::new(other_dir, false).unwrap();
+    assert_ne!(game, other);
+
+    let games: HashSet<_> = [game, linked, other].into_iter().collect();
+    assert_eq!(games.len(), 2);
+}
+
+#[test]
+fn test_find_games() {
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let game_a = create_test_game(&tmp_dir.path().join("a"));
+    let game_b = create_test_game(&tmp_dir.path().join("nested/b"));
+    // a game shipped inside another game is not found separately
+    create_test_game(&game_a.join("www/extras"));
+    // not a game
+    fs::create_dir_all(tmp_dir.path().join("empty/www")).unwrap();
+
+    let games = crate::find_games(tmp_dir.path())
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+
+    let paths: Vec<_> = games.iter().map(RpgGame::path).collect();
+    assert_eq!(paths, vec![game_a.as_path(), game_b.as_path()]);
+}
+
+#[test]
+fn test_signature_match_version_bytes() {
+    let tmp_dir = TempDir::new("rrd-test").unwrap();
+    let game_dir = create_test_game(tmp_dir.path());
+    let mut data = IMG_ENC.to_vec();
+    data[9] = 0x04; // different version
+    fs::write(game_dir.join("www/img/pictures/other.rpgmvp"), &data).unwrap();
+
+    let file = RpgFile::from_path(&game_dir.join("www/img/pictures/other.rpgmvp")).unwrap();
+    assert!(file.has_rpg_header());
+    assert!(file.has_rpg_header_with(SignatureMatch::Prefix));
+    assert!(!file.has_rpg_header_with(SignatureMatch::Full));
+
+    let mut game = RpgGame::new(&game_dir, false).unwrap();
+    assert!(game.open_encrypted("www/img/pictures/other.rpgmvp").is_ok());
+
+    game.set_signature_match(SignatureMatch::Full);
+    assert!(matches!(
+        game.open_encrypted("www/img/pictures/other.rpgmvp"),
+        Err(Error::FileNotEncrypted(_))
+    ));
+    assert!(game.open_encrypted("www/img/pictures/test.rpgmvp").is_ok());
+}
+
+#[test]
+fn test_signature_match_short_prefix() {
+    let file = unsafe {
+        RpgFile::from_parts(
+            b"RPGMV".to_vec(),
+            RpgFileType::Image,
+            PathBuf::from("a.rpgmvp"),
+        )
+    };
+
+    assert!(!file.has_rpg_header_with(SignatureMatch::Prefix));
+    assert!(!file.has_rpg_header_with(SignatureMatch::Full));
+    assert_eq!(file.decrypted_len(), 5);
+}
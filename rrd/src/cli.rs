@@ -9,8 +9,9 @@ pub struct Cli {
     /// The game directory
     pub game_dir: PathBuf,
 
-    #[command(subcommand)]
-    pub output: Option<OutputSettings>,
+    /// Where to put the decrypted files: in-place, overwrite, rename, specific:<dir>, flatten:<dir>, flatten-depth:<depth>:<dir>, by-type:<dir> or suffixed:<suffix>
+    #[arg(short, long, value_name = "MODE", default_value = "in-place", value_parser = parse_output_mode)]
+    pub mode: OutputSettings,
 
     /// Don't print individual files during decryption
     #[arg(short, long)]
@@ -23,4 +24,70 @@ pub struct Cli {
     /// Just print the key
     #[arg(short, long)]
     pub key: bool,
+
+    /// Also write the key to this file, as a hex string
+    #[arg(long, value_name = "PATH")]
+    pub key_output: Option<PathBuf>,
+
+    /// Write the raw key bytes instead of hex to the --key-output file
+    #[arg(long, requires = "key_output")]
+    pub raw: bool,
+
+    /// Use this System.json instead of looking for one in the game directory
+    #[arg(long, value_name = "PATH")]
+    pub system_json: Option<PathBuf>,
+
+    /// Lowercase the file names of the decrypted files
+    #[arg(long)]
+    pub lowercase: bool,
+
+    /// Write an index.json mapping the encrypted files to the decrypted ones
+    #[cfg(feature = "serde")]
+    #[arg(long)]
+    pub index: bool,
+
+    /// Only decrypt files referenced by the game's data files
+    #[arg(long)]
+    pub only_referenced: bool,
+
+    /// Make read-only files writable when they need to be overwritten
+    #[arg(long)]
+    pub clear_readonly: bool,
+
+    /// Choose the extension of the decrypted files based on their contents
+    #[arg(long)]
+    pub sniff_extensions: bool,
+
+    /// Skip files whose decrypted version already exists
+    #[arg(long)]
+    pub skip_existing: bool,
+
+    /// Decrypt into a staging directory that only becomes the output directory once every file succeeded (specific, flatten, flatten-depth and by-type modes only)
+    #[arg(long)]
+    pub staged: bool,
+
+    /// Decrypt even if the game reports that it is not encrypted
+    #[arg(long)]
+    pub force: bool,
+
+    /// Set the permissions of the decrypted files, in octal (eg. 644). Unix only
+    #[arg(long, value_name = "MODE", value_parser = parse_file_mode)]
+    pub file_mode: Option<u32>,
+
+    /// Fail files larger than this many bytes instead of reading them into memory
+    #[arg(long, value_name = "BYTES")]
+    pub max_file_size: Option<u64>,
+
+    /// Leave System.json untouched, even when replacing the game's files
+    #[arg(long)]
+    pub no_update_encryption: bool,
+}
+
+fn parse_output_mode(mode: &str) -> Result<OutputSettings, String> {
+    mode.parse()
+        .map_err(|e: librpgmaker::error::Error| e.to_string())
+}
+
+fn parse_file_mode(mode: &str) -> Result<u32, String> {
+    u32::from_str_radix(mode.trim_start_matches("0o"), 8).map_err(|e| e.to_string())
 }
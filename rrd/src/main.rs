@@ -1,4 +1,4 @@
-use std::{fmt::Display, process::exit, time::Instant};
+use std::{fmt::Display, fs, process::exit};
 
 use clap::Parser;
 use cli::*;
@@ -10,13 +10,48 @@ mod cli;
 fn main() {
     let args = Cli::parse();
 
-    let mut game = RpgGame::new(args.game_dir, !args.quiet).unwrap_or_else(|e| {
-        eprintln!("Failed to open game dir: {}", e);
+    let game = match &args.system_json {
+        Some(system_json) => RpgGame::with_system_json(&args.game_dir, system_json, !args.quiet),
+        None => RpgGame::new(&args.game_dir, !args.quiet),
+    };
+    let mut game = game.unwrap_or_else(|e| {
+        match e {
+            Error::NotEncrypted => {
+                eprintln!("No key found, System.json contains no encryption key")
+            }
+            e => eprintln!("Failed to open game dir: {}", e),
+        }
         exit(1);
     });
 
+    game.set_no_update_encryption(args.no_update_encryption);
+    game.set_lowercase_output(args.lowercase);
+    #[cfg(feature = "serde")]
+    game.set_write_index(args.index);
+    game.set_only_referenced(args.only_referenced);
+    game.set_clear_readonly(args.clear_readonly);
+    game.set_sniff_extensions(args.sniff_extensions);
+    game.set_force(args.force);
+    game.set_staged_output(args.staged);
+    game.set_skip_if_decrypted_exists(args.skip_existing);
+    game.set_file_mode(args.file_mode);
+    game.set_max_file_size(args.max_file_size);
+
     pretty_print_key(&game);
 
+    if let Some(key_output) = &args.key_output {
+        let key = game.key();
+        let data = match args.raw {
+            true => key.as_bytes().to_vec(),
+            false => key.to_hex().into_bytes(),
+        };
+
+        if let Err(e) = fs::write(key_output, data) {
+            eprintln!("Failed to write the key to {}: {}", key_output.display(), e);
+            exit(1);
+        }
+    }
+
     if args.key {
         exit(0);
     }
@@ -35,26 +70,24 @@ fn main() {
         exit(0);
     }
 
-    let start_time = Instant::now();
-    let results = match game.decrypt_all(&args.output.unwrap_or(OutputSettings::NextTo)) {
+    let report = match game.decrypt_all_report(&args.mode) {
         Ok(v) => v,
+        Err(e @ Error::AlreadyDecrypted) => {
+            println!("{}. Use --force to decrypt anyway.", e);
+            exit(0);
+        }
         Err(e) => {
             eprintln!("Failed to decryptt the game: {}", e);
             exit(1);
         }
     };
-    let results_len = results.len();
-
-    let failed = results
-        .into_iter()
-        .filter_map(|x| x.err())
-        .collect::<Vec<_>>();
+    let failed = &report.failed;
 
     println!("\n");
     if !failed.is_empty() {
         println!("\n");
 
-        for error in &failed {
+        for error in failed {
             eprintln!("ERROR: {}", error);
         }
         print!(
@@ -66,11 +99,16 @@ fn main() {
     }
 
     println!(
-        "\n\nDecrypted {}/{} files in {:.2?}",
-        results_len - failed.len(),
+        "\n\nDecrypted {}/{} files in {:.2?} ({:.2} MiB/s)",
+        report.decrypted,
         scanned.len(),
-        start_time.elapsed()
+        report.duration,
+        report.bytes_per_second / (1024.0 * 1024.0)
     );
+
+    if !failed.is_empty() {
+        exit(1);
+    }
 }
 
 fn pretty_print_key(game: &RpgGame) {
@@ -85,33 +123,43 @@ fn pretty_print_key(game: &RpgGame) {
     println!("Found the following key:\n");
 
     println!("  Text : {}", key.string);
-    println!("  Bytes: {:02X?}\n", key.bytes);
+    println!("  Bytes: {:02X?}", key.bytes);
+    println!("  From : {}\n", game.key_source());
+
+    if !game.key_looks_valid() {
+        println!(
+            "WARNING: The key is {} bytes long instead of the usual 16, it might be wrong.\n",
+            key.bytes.len()
+        );
+    }
 }
 
 fn count_variants<'a>(items: impl Iterator<Item = &'a RpgFileType>) -> Counts {
     let counts = items.counts();
 
-    Counts {
-        audio: *counts.get(&RpgFileType::Audio).unwrap_or(&0),
-        video: *counts.get(&RpgFileType::Video).unwrap_or(&0),
-        image: *counts.get(&RpgFileType::Image).unwrap_or(&0),
-    }
+    Counts(
+        RpgFileType::all()
+            .into_iter()
+            .map(|file_type| {
+                let count = *counts.get(&file_type).unwrap_or(&0);
+                (file_type, count)
+            })
+            .collect(),
+    )
 }
 
 #[derive(Debug)]
-struct Counts {
-    audio: usize,
-    video: usize,
-    image: usize,
-}
+struct Counts(Vec<(RpgFileType, usize)>);
 
 impl Display for Counts {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let total = self.audio + self.video + self.image;
-        write!(
-            f,
-            "Found {} decryptable items:\n\n   - images: {}\n   - audios: {}\n   - videos: {}\n",
-            total, self.image, self.audio, self.video
-        )
+        let total: usize = self.0.iter().map(|(_, count)| count).sum();
+        writeln!(f, "Found {} decryptable items:\n", total)?;
+
+        for (file_type, count) in &self.0 {
+            writeln!(f, "   - {}s: {}", file_type.name(), count)?;
+        }
+
+        Ok(())
     }
 }